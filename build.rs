@@ -38,4 +38,9 @@ fn main() {
     if rustc::is_min_version("1.61.0").unwrap_or(false) {
         println!("cargo:rustc-cfg=feature=\"rustc_test_Ignore_messages\"");
     }
+
+    // See src/runner.rs
+    if rustc::is_min_version("1.97.0").unwrap_or(false) {
+        println!("cargo:rustc-cfg=feature=\"rustc_test_TestList\"");
+    }
 }
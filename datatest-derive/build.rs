@@ -0,0 +1,55 @@
+use std::env;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Nightly-only API used to tell the compiler which data files tests depend on.
+const TRACKED_PATH_PROBE: &str = r#"
+#![feature(proc_macro_tracked_path)]
+extern crate proc_macro;
+
+#[allow(dead_code)]
+fn probe() {
+    proc_macro::tracked::path("");
+}
+"#;
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rustc-check-cfg=cfg(feature, values(\"rustc_has_tracked_path\"))");
+
+    // See src/track.rs
+    if probe(TRACKED_PATH_PROBE) {
+        println!("cargo:rustc-cfg=feature=\"rustc_has_tracked_path\"");
+    }
+}
+
+/// Check if the given proc-macro crate source compiles with the current compiler.
+fn probe(source: &str) -> bool {
+    let rustc = env::var_os("RUSTC").unwrap_or_else(|| "rustc".into());
+    let out_dir = match env::var_os("OUT_DIR") {
+        Some(dir) => dir,
+        None => return false,
+    };
+    let child = Command::new(rustc)
+        .args([
+            "--crate-type=proc-macro",
+            "--crate-name=datatest_probe",
+            "--emit=metadata",
+            "--out-dir",
+        ])
+        .arg(out_dir)
+        .arg("-")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(_) => return false,
+    };
+    let written = child
+        .stdin
+        .take()
+        .is_some_and(|mut stdin| stdin.write_all(source.as_bytes()).is_ok());
+    child.wait().is_ok_and(|status| status.success()) && written
+}
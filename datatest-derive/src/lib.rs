@@ -1,7 +1,13 @@
 #![recursion_limit = "128"]
 #![deny(unused_must_use)]
+#![cfg_attr(feature = "rustc_has_tracked_path", feature(proc_macro_tracked_path))]
 extern crate proc_macro;
 
+mod paths;
+mod track;
+mod validate;
+
+use crate::paths::DataPath;
 use proc_macro2::{Span, TokenStream};
 use quote::quote;
 use std::collections::HashMap;
@@ -9,13 +15,20 @@ use syn::parse::{Parse, ParseStream, Result as ParseResult};
 use syn::punctuated::Punctuated;
 use syn::spanned::Spanned;
 use syn::token::Comma;
-use syn::{braced, parse_macro_input, FnArg, Ident, ItemFn, Pat, PatIdent, PatType, Token, Type};
+use syn::{
+    braced, bracketed, parse_macro_input, FnArg, Ident, ItemFn, Pat, PatIdent, PatType, Token, Type,
+};
 
 type Error = syn::parse::Error;
 
 struct TemplateArg {
     ident: syn::Ident,
     is_pattern: bool,
+    is_glob: bool,
+    /// Argument takes the value of the pattern capture group instead of a file
+    is_capture: bool,
+    /// Matched file is split into test cases (`per line` or `per record`)
+    split: Option<Ident>,
     ignore_fn: Option<syn::Path>,
     value: syn::LitStr,
 }
@@ -23,16 +36,44 @@ struct TemplateArg {
 impl Parse for TemplateArg {
     fn parse(input: ParseStream) -> ParseResult<Self> {
         let mut ignore_fn = None;
+        let mut is_glob = false;
+        let mut is_capture = false;
         let ident = input.parse::<syn::Ident>()?;
 
         let is_pattern = if input.peek(syn::token::In) {
             let _in = input.parse::<syn::token::In>()?;
             true
+        } else if input.peek(syn::Ident) {
+            let kind = input.parse::<syn::Ident>()?;
+            if kind != "glob" {
+                return Err(Error::new(kind.span(), "expected `in`, `glob` or `=`"));
+            }
+            is_glob = true;
+            true
         } else {
             let _eq = input.parse::<syn::token::Eq>()?;
+            if input.peek(syn::Ident) {
+                let kind = input.parse::<syn::Ident>()?;
+                if kind != "capture" {
+                    return Err(Error::new(kind.span(), "expected `capture` or a template"));
+                }
+                is_capture = true;
+            }
             false
         };
         let value = input.parse::<syn::LitStr>()?;
+        let mut split = None;
+        if is_pattern && input.peek(syn::Ident) {
+            let per = input.parse::<syn::Ident>()?;
+            if per != "per" {
+                return Err(Error::new(per.span(), "expected `per`, `if` or `,`"));
+            }
+            let unit = input.parse::<syn::Ident>()?;
+            if unit != "line" && unit != "record" {
+                return Err(Error::new(unit.span(), "expected `line` or `record`"));
+            }
+            split = Some(unit);
+        }
         if is_pattern && input.peek(syn::token::If) {
             let _if = input.parse::<syn::token::If>()?;
             let _not = input.parse::<syn::token::Not>()?;
@@ -41,6 +82,9 @@ impl Parse for TemplateArg {
         Ok(Self {
             ident,
             is_pattern,
+            is_glob,
+            is_capture,
+            split,
             ignore_fn,
             value,
         })
@@ -52,13 +96,40 @@ impl Parse for TemplateArg {
 ///
 /// ```ignore
 /// #[files("<root>", {
-///   <arg_name> in "<regexp>",
-///   <arg_name> in "<template>",
-/// }]
+///   <arg_name> in "<regexp>", // or <arg_name> glob "<glob>"; could be followed by `per line`
+///                             // or `per record` to split matched file into test cases
+///   <arg_name> = "<template>",
+///   <arg_name> = capture "<name or index>",
+/// }, <option> = <value>, ...]
 /// ```
+///
+/// The following options are supported:
+/// * `directories = <bool>`: match pattern against directories instead of files
+/// * `directives = <bool>`: read directives from the header of the matched files (default is
+///   `true`)
+/// * `name = "<template>"`: template for the test case name (rendered from the pattern captures)
+/// * `xfail = "<path>"`: manifest listing test cases expected to fail
+/// * `exclude = "<glob>"` or `exclude = ["<glob>", ...]`: paths (relative to the root) to skip
+/// * `gitignore = <bool>`: skip paths ignored via `.gitignore` and `.ignore` files
+/// * `max_depth = <int>`: maximum depth of the directories to scan (`1` means root only)
+/// * `follow_links = <bool>`: follow symbolic links (default is `true`)
+/// * `include_hidden = <bool>`: include hidden files and directories (default is `false`)
+/// * `prune_hidden = <bool>`: do not scan hidden directories (default is `false`)
+/// * `sort = <bool>`: sort directory entries by name (default is `true`)
 struct FilesTestArgs {
-    root: String,
+    root: syn::LitStr,
     args: HashMap<Ident, TemplateArg>,
+    directories: bool,
+    directives: bool,
+    name: Option<syn::LitStr>,
+    xfail: Option<syn::LitStr>,
+    exclude: Vec<syn::LitStr>,
+    gitignore: bool,
+    max_depth: Option<syn::LitInt>,
+    follow_links: bool,
+    include_hidden: bool,
+    prune_hidden: bool,
+    sort: bool,
 }
 
 /// See `syn` crate documentation / sources for more examples.
@@ -79,9 +150,70 @@ impl Parse for FilesTestArgs {
             })
             .collect();
 
+        let mut directories = false;
+        let mut directives = true;
+        let mut name = None;
+        let mut xfail = None;
+        let mut exclude = Vec::new();
+        let mut gitignore = false;
+        let mut max_depth = None;
+        let mut follow_links = true;
+        let mut include_hidden = false;
+        let mut prune_hidden = false;
+        let mut sort = true;
+        while !input.is_empty() {
+            let _comma = input.parse::<syn::token::Comma>()?;
+            if input.is_empty() {
+                break;
+            }
+            let option = input.parse::<syn::Ident>()?;
+            let _eq = input.parse::<syn::token::Eq>()?;
+            match option.to_string().as_str() {
+                "directories" => directories = input.parse::<syn::LitBool>()?.value,
+                "directives" => directives = input.parse::<syn::LitBool>()?.value,
+                "gitignore" => gitignore = input.parse::<syn::LitBool>()?.value,
+                "max_depth" => {
+                    let depth = input.parse::<syn::LitInt>()?;
+                    depth.base10_parse::<usize>()?;
+                    max_depth = Some(depth);
+                }
+                "follow_links" => follow_links = input.parse::<syn::LitBool>()?.value,
+                "include_hidden" => include_hidden = input.parse::<syn::LitBool>()?.value,
+                "prune_hidden" => prune_hidden = input.parse::<syn::LitBool>()?.value,
+                "sort" => sort = input.parse::<syn::LitBool>()?.value,
+                "exclude" if input.peek(syn::token::Bracket) => {
+                    let content;
+                    let _bracket_token = bracketed!(content in input);
+                    let patterns: Punctuated<syn::LitStr, Comma> = content
+                        .parse_terminated(|input| input.parse::<syn::LitStr>(), Token![,])?;
+                    exclude.extend(patterns);
+                }
+                "exclude" => exclude.push(input.parse::<syn::LitStr>()?),
+                "name" => name = Some(input.parse::<syn::LitStr>()?),
+                "xfail" => xfail = Some(input.parse::<syn::LitStr>()?),
+                _ => {
+                    return Err(Error::new(
+                        option.span(),
+                        format!("unknown option `{}`", option),
+                    ))
+                }
+            }
+        }
+
         Ok(Self {
-            root: root.value(),
+            root,
             args,
+            directories,
+            directives,
+            name,
+            xfail,
+            exclude,
+            gitignore,
+            max_depth,
+            follow_links,
+            include_hidden,
+            prune_hidden,
+            sort,
         })
     }
 }
@@ -138,34 +270,42 @@ pub fn files_test_case_internal(
 
 /// Proc macro handling `#[files(...)]` syntax. This attribute defines rules for deriving
 /// test function arguments from file paths. There are two types of rules:
-/// 1. Pattern rule, `<arg_name> in "<regexp>"`
+/// 1. Pattern rule, `<arg_name> in "<regexp>"` (or `<arg_name> glob "<glob>"`)
 /// 2. Template rule, `<arg_name> = "regexp"`
+/// 3. Capture rule, `<arg_name> = capture "<name>"`, which gives the value of the pattern capture
+///    group
+///
+/// Pattern rule could be followed by `per line` or `per record`, in which case each matched file is
+/// split into multiple test cases and the pattern argument gets the contents of the test case.
 ///
 /// There must be only one pattern rule defined in the attribute. It defines a regular expression
-/// to run against all files found in the test directory.
+/// (or a glob pattern) to run against all files found in the test directory.
 ///
 /// Template rule defines rules how the name of the matched file is transformed to get related files.
 ///
 /// This macro is responsible for generating a test descriptor (`datatest::FilesTestDesc`) based on the
 /// `#[files(..)]` attribute attached to the test function.
 ///
-/// There are four fields specific for these type of tests we need to fill in:
+/// There are five fields specific for these type of tests we need to fill in:
 ///
 /// 1. `root`, which is the root directory to scan for the tests (relative to the root of the crate
-/// with tests)
+///    with tests)
 /// 2. `params`, slice of strings, each string is either a template or pattern assigned to the
-/// function argument
+///    function argument
 /// 3. `pattern`, an index of the "pattern" argument (since exactly one is required, it is just an
-/// index in the `params` array).
-/// 4. `testfn`, test function trampoline.
+///    index in the `params` array).
+/// 4. `captures`, indices of the arguments bound to the pattern capture groups (their templates
+///    give the captured value instead of the file path)
+/// 5. `testfn`, test function trampoline.
 ///
 /// Few words about trampoline function. Each test function could have a unique signature, depending
 /// on which types it needs and which files it requires as an input. However, our test framework
 /// should be capable of running these test functions via some standardized interface. This interface
-/// is `fn(&[PathBuf])`. Each slice element matches test function argument (so length of this slice
-/// is the same as amount of arguments test function has).
+/// is `fn(&[TestArg])`. Each slice element matches test function argument (so length of this slice
+/// is the same as amount of arguments test function has). Element is either a path to the data file
+/// or a text (captured value or contents of the test case, if the matched file is split).
 ///
-/// In addition to that, this trampoline function is also responsible for mapping `&PathBuf`
+/// In addition to that, this trampoline function is also responsible for mapping `&Path`
 /// references into argument types. There is some trait magic involved to make code work for both
 /// cases when function takes argument as a slice (`&str`, `&[u8]`) and for cases when function takes
 /// argument as owned (`String`, `Vec<u8>`).
@@ -174,9 +314,9 @@ pub fn files_test_case_internal(
 /// function. However, for arguments taking slices, we need to store value somewhere on the stack
 /// and pass a reference.
 ///
-/// I could have made this proc macro to handle these cases explicitly and generate a different
-/// code, but I decided to not add a complexity of type analysis to the proc macro and use traits
-/// instead. See `datatest::TakeArg` and `datatest::DeriveArg` to see how this mechanism works.
+/// This proc macro only looks at the syntax of the argument type to tell borrowed arguments (`&T`
+/// and `Option<&T>`) from the owned ones and the rest is done via traits. See `datatest::TakeArg`,
+/// `datatest::DeriveArg` and `datatest::FromTestFile` to see how this mechanism works.
 fn files_internal(
     args: proc_macro::TokenStream,
     func: proc_macro::TokenStream,
@@ -184,7 +324,7 @@ fn files_internal(
 ) -> proc_macro::TokenStream {
     let mut func_item: ItemFn = parse_macro_input!(func as ItemFn);
     let args: FilesTestArgs = parse_macro_input!(args as FilesTestArgs);
-    let info = handle_common_attrs(&mut func_item, false);
+    let info = handle_common_attrs(&mut func_item);
     let func_ident = &func_item.sig.ident;
     let func_name_str = func_ident.to_string();
     let desc_ident = Ident::new(&format!("__TEST_{}", func_ident), func_ident.span());
@@ -193,16 +333,28 @@ fn files_internal(
         func_ident.span(),
     );
     let ignore = info.ignore;
-    let root = args.root;
+    let should_panic = info.should_panic.to_tokens();
+    let root = match DataPath::resolve(&args.root.value()) {
+        Ok(root) => root,
+        Err(err) => return Error::new(args.root.span(), err).to_compile_error().into(),
+    };
+    let directories = args.directories;
+    let directives = args.directives;
+    let is_archive = datatest_internals::is_archive(&root.relative);
     let mut pattern_idx = None;
     let mut params: Vec<String> = Vec::new();
     let mut invoke_args: Vec<TokenStream> = Vec::new();
     let mut ignore_fn = None;
+    let mut glob = false;
+    let mut pattern_arg = None;
+    let mut templates = Vec::new();
+    let mut captures = Vec::new();
+    let mut split = None;
 
     // Match function arguments with our parsed list of mappings
     // We do the following in this loop:
     // 1. For each argument we collect the corresponding template defined for that argument
-    // 2. For each argument we collect piece of code to create argument from the `&[PathBuf]` slice
+    // 2. For each argument we collect piece of code to create argument from the `&[TestArg]` slice
     // given to us by the test runner.
     // 3. Capture the index of the argument corresponding to the "pattern" mapping
     for (mut idx, arg) in func_item.sig.inputs.iter().enumerate() {
@@ -220,6 +372,14 @@ fn files_internal(
 
                 if let Some(arg) = args.args.get(&pat_ident.ident) {
                     if arg.is_pattern {
+                        if option_inner(ty).is_some() {
+                            return Error::new(
+                                ty.span(),
+                                "pattern argument cannot be optional, matched file always exists",
+                            )
+                            .to_compile_error()
+                            .into();
+                        }
                         if pattern_idx.is_some() {
                             return Error::new(arg.ident.span(), "two patterns are not allowed!")
                                 .to_compile_error()
@@ -227,12 +387,32 @@ fn files_internal(
                         }
                         pattern_idx = Some(idx);
                         ignore_fn = arg.ignore_fn.clone();
+                        glob = arg.is_glob;
+                        split = arg.split.as_ref();
+                        pattern_arg = Some(arg);
                     }
 
+                    if arg.is_capture {
+                        captures.push(idx);
+                    }
+                    // Test case contents are passed the same way as captured values
+                    if arg.is_capture || arg.split.is_some() {
+                        invoke_args.push(quote! {
+                            ::std::convert::From::from(test_args[#idx].text())
+                        });
+                    } else {
+                        if is_archive && is_path(ty) {
+                            return Error::new(
+                                ty.span(),
+                                "archive entries cannot be opened via a path; use `&str`, `String`, `&[u8]` or `Vec<u8>` to get the contents instead",
+                            )
+                            .to_compile_error()
+                            .into();
+                        }
+                        invoke_args.push(derive_arg(ty, quote!(test_args[#idx].path())));
+                    }
+                    templates.push(arg);
                     params.push(arg.value.value());
-                    invoke_args.push(quote! {
-                        ::datatest::__internal::TakeArg::take(&mut <#ty as ::datatest::__internal::DeriveArg>::derive(&paths_arg[#idx]))
-                    })
                 } else {
                     return Error::new(pat_ident.span(), "mapping is not defined for the argument")
                         .to_compile_error()
@@ -242,12 +422,65 @@ fn files_internal(
             None => {
                 return Error::new(
                     arg.span(),
-                    "unexpected argument; only simple argument types are allowed (`&str`, `String`, `&[u8]`, `Vec<u8>`, `&Path`, etc)",
+                    "unexpected argument; only simple argument types are allowed (`&str`, `String`, `&[u8]`, `Vec<u8>`, `&Path`, `Option<&str>`, etc)",
                 ).to_compile_error().into();
             }
         }
     }
 
+    for pattern in &args.exclude {
+        if let Err(err) = validate::Captures::from_glob(&pattern.value()) {
+            return Error::new(pattern.span(), err).to_compile_error().into();
+        }
+    }
+    if let Some(pattern) = pattern_arg {
+        if let Err(err) = validate_templates(pattern, &templates, args.name.as_ref()) {
+            return err.to_compile_error().into();
+        }
+        // Captures are rendered as templates referring to the capture group
+        for &idx in &captures {
+            params[idx] = if pattern.is_glob {
+                format!("{{{}}}", params[idx])
+            } else {
+                format!("${{{}}}", params[idx])
+            };
+        }
+    }
+    if let Some(ref full) = root.full {
+        if let Err(err) = validate::check_root(&args.root.value(), full) {
+            return Error::new(args.root.span(), err).to_compile_error().into();
+        }
+    }
+
+    let case_name = match args.name {
+        Some(ref name) => quote!(Some(#name)),
+        None => quote!(None),
+    };
+    let split = match split {
+        Some(unit) if directories => {
+            return Error::new(unit.span(), "directories cannot be split into test cases")
+                .to_compile_error()
+                .into()
+        }
+        Some(unit) if unit == "line" => quote!(Some(::datatest::__internal::Split::Line)),
+        Some(_) => quote!(Some(::datatest::__internal::Split::Record)),
+        None => quote!(None),
+    };
+    let xfail = match resolve_optional(args.xfail.as_ref()) {
+        Ok(xfail) => xfail,
+        Err(err) => return err.to_compile_error().into(),
+    };
+    let exclude = &args.exclude;
+    let gitignore = args.gitignore;
+    let max_depth = match args.max_depth {
+        Some(ref depth) => quote!(Some(#depth)),
+        None => quote!(None),
+    };
+    let follow_links = args.follow_links;
+    let include_hidden = args.include_hidden;
+    let prune_hidden = args.prune_hidden;
+    let sort = args.sort;
+
     let ignore_func_ref = if let Some(ignore_fn) = ignore_fn {
         quote!(Some(#ignore_fn))
     } else {
@@ -257,7 +490,7 @@ fn files_internal(
     if pattern_idx.is_none() {
         return Error::new(
             Span::call_site(),
-            "must have exactly one pattern mapping defined via `pattern in r#\"<regular expression>\"`",
+            "must have exactly one pattern mapping defined via `pattern in r#\"<regular expression>\"` or `pattern glob \"<glob>\"`",
         )
             .to_compile_error()
             .into();
@@ -272,17 +505,57 @@ fn files_internal(
         (quote!(TestFn), quote!())
     };
 
+    // Only track files the test could use (see `track` module)
+    let filter = track::FilesFilter {
+        pattern: &params[pattern_idx.unwrap()],
+        glob,
+        templates: params
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| Some(*idx) != pattern_idx && !captures.contains(idx))
+            .map(|(_, template)| template.as_str())
+            .collect(),
+        directories,
+        exclude: args.exclude.iter().map(|pattern| pattern.value()).collect(),
+        max_depth: args
+            .max_depth
+            .as_ref()
+            .and_then(|depth| depth.base10_parse().ok()),
+        include_hidden: args.include_hidden,
+        prune_hidden: args.prune_hidden,
+    };
+    let tracking = track::track_files(&root, &filter);
+    let base_dir = &root.base;
+    let root = &root.relative;
     let registration = test_registration(channel, &desc_ident);
     let output = quote! {
+        #tracking
+
         #registration
         #[automatically_derived]
         #[allow(non_upper_case_globals)]
         static #desc_ident: ::datatest::__internal::FilesTestDesc = ::datatest::__internal::FilesTestDesc {
             name: concat!(module_path!(), "::", #func_name_str),
             ignore: #ignore,
+            should_panic: #should_panic,
             root: #root,
+            base_dir: #base_dir,
             params: &[#(#params),*],
             pattern: #pattern_idx,
+            glob: #glob,
+            directories: #directories,
+            directives: #directives,
+            captures: &[#(#captures),*],
+            split: #split,
+            case_name: #case_name,
+            xfail: #xfail,
+            exclude: &[#(#exclude),*],
+            gitignore: #gitignore,
+            max_depth: #max_depth,
+            follow_links: #follow_links,
+            include_hidden: #include_hidden,
+            prune_hidden: #prune_hidden,
+            sort: #sort,
             ignorefn: #ignore_func_ref,
             testfn: ::datatest::__internal::FilesTestFn::#kind(#trampoline_func_ident),
             source_file: file!(),
@@ -290,7 +563,7 @@ fn files_internal(
 
         #[automatically_derived]
         #[allow(non_snake_case)]
-        fn #trampoline_func_ident(#bencher_param paths_arg: &[::std::path::PathBuf]) {
+        fn #trampoline_func_ident(#bencher_param test_args: &[::datatest::__internal::TestArg]) {
             let result = #func_ident(#(#invoke_args),*);
             ::datatest::__internal::assert_test_result(result);
         }
@@ -300,6 +573,57 @@ fn files_internal(
     output.into()
 }
 
+/// Check that pattern is valid and templates (including the test case name template) only refer to
+/// the capture groups defined by it.
+fn validate_templates(
+    pattern: &TemplateArg,
+    templates: &[&TemplateArg],
+    case_name: Option<&syn::LitStr>,
+) -> Result<(), Error> {
+    let value = pattern.value.value();
+    let captures = if pattern.is_glob {
+        validate::Captures::from_glob(&value)
+    } else {
+        validate::Captures::from_regex(&value)
+    };
+    let captures = captures.map_err(|err| Error::new(pattern.value.span(), err))?;
+    let check_template = |value: &str| {
+        if pattern.is_glob {
+            captures.check_glob_template(value)
+        } else {
+            captures.check_regex_template(value)
+        }
+    };
+    for template in templates {
+        let value = template.value.value();
+        let result = if template.is_pattern {
+            continue;
+        } else if template.is_capture {
+            captures.check_capture(&value)
+        } else {
+            check_template(&value)
+        };
+        result.map_err(|err| Error::new(template.value.span(), err))?;
+    }
+    if let Some(case_name) = case_name {
+        check_template(&case_name.value()).map_err(|err| Error::new(case_name.span(), err))?;
+    }
+    Ok(())
+}
+
+/// Generate `Option<&'static str>` expression with the full path to the optional data file.
+fn resolve_optional(path: Option<&syn::LitStr>) -> Result<TokenStream, Error> {
+    match path {
+        Some(path) => {
+            let resolved =
+                DataPath::resolve(&path.value()).map_err(|err| Error::new(path.span(), err))?;
+            let full_path = resolved.full_path_expr();
+            Ok(quote!(Some(#full_path)))
+        }
+        None => Ok(quote!(None)),
+    }
+}
+
 fn match_arg(arg: &FnArg) -> Option<(&PatIdent, &Type)> {
     if let FnArg::Typed(PatType { pat, ty, .. }) = arg {
         if let Pat::Ident(pat_ident) = pat.as_ref() {
@@ -309,21 +633,87 @@ fn match_arg(arg: &FnArg) -> Option<(&PatIdent, &Type)> {
     None
 }
 
+/// Generate an expression converting a path into the function argument of the given type.
+fn derive_arg(ty: &Type, path: TokenStream) -> TokenStream {
+    let inner = option_inner(ty);
+    match inner.unwrap_or(ty) {
+        // Borrowed arguments need a temporary value to borrow from
+        Type::Reference(_) => quote! {
+            ::datatest::__internal::TakeArg::take(&mut <#ty as ::datatest::__internal::DeriveArg>::derive(#path))
+        },
+        inner_ty if inner.is_some() => quote! {
+            ::datatest::__internal::load_optional::<#inner_ty>(#path)
+        },
+        _ => quote! {
+            ::datatest::__internal::load_arg::<#ty>(#path)
+        },
+    }
+}
+
+/// Check if the type is `&Path` or `PathBuf` (or optional one). Only looks at the syntax, so type
+/// aliases are not recognized.
+fn is_path(ty: &Type) -> bool {
+    let ty = match option_inner(ty).unwrap_or(ty) {
+        Type::Reference(reference) => &*reference.elem,
+        ty => ty,
+    };
+    match ty {
+        Type::Path(type_path) => type_path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Path" || segment.ident == "PathBuf"),
+        _ => false,
+    }
+}
+
+/// If the type is `Option<T>`, returns `T`. Only looks at the syntax, so type aliases are not
+/// recognized.
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let path = match ty {
+        Type::Path(type_path) if type_path.qself.is_none() => &type_path.path,
+        _ => return None,
+    };
+    let segment = path.segments.last()?;
+    if segment.ident != "Option" {
+        return None;
+    }
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) if args.args.len() == 1 => match &args.args[0] {
+            syn::GenericArgument::Type(inner) => Some(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
 enum ShouldPanic {
     No,
     Yes,
     YesWithMessage(String),
 }
 
+impl ShouldPanic {
+    fn to_tokens(&self) -> TokenStream {
+        match self {
+            ShouldPanic::No => quote!(::datatest::__internal::RegularShouldPanic::No),
+            ShouldPanic::Yes => quote!(::datatest::__internal::RegularShouldPanic::Yes),
+            ShouldPanic::YesWithMessage(v) => {
+                quote!(::datatest::__internal::RegularShouldPanic::YesWithMessage(#v))
+            }
+        }
+    }
+}
+
 struct FuncInfo {
     ignore: bool,
     bench: bool,
     should_panic: ShouldPanic,
 }
 
-/// Only allows certain attributes (`#[should_panic]`, for example) when used against a "regular"
-/// test `#[test]`.
-fn handle_common_attrs(func: &mut ItemFn, regular_test: bool) -> FuncInfo {
+/// Handle attributes common to all kinds of tests (`#[test]`, `#[bench]`, `#[ignore]` and
+/// `#[should_panic]`), removing them from the function.
+fn handle_common_attrs(func: &mut ItemFn) -> FuncInfo {
     // Remove #[test] attribute as we don't want standard test framework to handle it!
     // We allow #[test] to be used to improve IDE experience (namely, IntelliJ Rust), which would
     // only allow you to run test if it is marked with `#[test]`
@@ -353,18 +743,17 @@ fn handle_common_attrs(func: &mut ItemFn, regular_test: bool) -> FuncInfo {
         func.attrs.remove(pos);
     }
 
+    // Allow `#[should_panic]`: for regular tests on stable channel, and for files and data tests
+    // (applies to every test case)
     let mut should_panic = ShouldPanic::No;
-    if regular_test {
-        // Regular tests support (on stable channel): allow `#[should_panic]`
-        let should_panic_pos = func
-            .attrs
-            .iter()
-            .position(|attr| attr.path().is_ident("should_panic"));
-        if let Some(pos) = should_panic_pos {
-            let attr = &func.attrs[pos];
-            should_panic = parse_should_panic(attr);
-            func.attrs.remove(pos);
-        }
+    let should_panic_pos = func
+        .attrs
+        .iter()
+        .position(|attr| attr.path().is_ident("should_panic"));
+    if let Some(pos) = should_panic_pos {
+        let attr = &func.attrs[pos];
+        should_panic = parse_should_panic(attr);
+        func.attrs.remove(pos);
     }
 
     FuncInfo {
@@ -399,10 +788,26 @@ fn parse_should_panic(attr: &syn::Attribute) -> ShouldPanic {
 }
 
 /// Parse `#[data(...)]` attribute arguments. It's either a function returning
-/// `Vec<datatest::DataTestCaseDesc<T>>` (where `T` is a test case type) or string literal, which
-/// is interpreted as `datatest::yaml("<path>")`
+/// `Vec<datatest::DataTestCaseDesc<T>>` (where `T` is a test case type, or `Result<T, String>`
+/// for test cases which could fail to load) or string literal, which
+/// is interpreted as `datatest::yaml("<path>")` (or other loader, depending on the file extension,
+/// like `datatest::json("<path>")`), optionally followed by options. Call of the `datatest`
+/// loader with a string literal (like `datatest::yaml_documents("<path>")`) resolves the path the
+/// same way as the string literal does.
+///
+/// ```ignore
+/// #[data("<path>", <option> = <value>, ...)]
+/// ```
+///
+/// The following options are supported:
+/// * `xfail = "<path>"`: manifest listing test cases expected to fail
+struct DataTestArgs {
+    cases: DataTestCases,
+    xfail: Option<syn::LitStr>,
+}
+
 #[allow(clippy::large_enum_variant)]
-enum DataTestArgs {
+enum DataTestCases {
     Literal(syn::LitStr),
     Expression(syn::Expr),
 }
@@ -411,11 +816,31 @@ enum DataTestArgs {
 impl Parse for DataTestArgs {
     fn parse(input: ParseStream) -> ParseResult<Self> {
         let lookahead = input.lookahead1();
-        if lookahead.peek(syn::LitStr) {
-            input.parse::<syn::LitStr>().map(DataTestArgs::Literal)
+        let cases = if lookahead.peek(syn::LitStr) {
+            input.parse::<syn::LitStr>().map(DataTestCases::Literal)?
         } else {
-            input.parse::<syn::Expr>().map(DataTestArgs::Expression)
+            input.parse::<syn::Expr>().map(DataTestCases::Expression)?
+        };
+
+        let mut xfail = None;
+        while !input.is_empty() {
+            let _comma = input.parse::<syn::token::Comma>()?;
+            if input.is_empty() {
+                break;
+            }
+            let option = input.parse::<syn::Ident>()?;
+            let _eq = input.parse::<syn::token::Eq>()?;
+            match option.to_string().as_str() {
+                "xfail" => xfail = Some(input.parse::<syn::LitStr>()?),
+                _ => {
+                    return Err(Error::new(
+                        option.span(),
+                        format!("unknown option `{}`", option),
+                    ))
+                }
+            }
         }
+        Ok(Self { cases, xfail })
     }
 }
 
@@ -461,17 +886,93 @@ pub fn data_test_case_internal(
     data_internal(args, func, Registration::Nightly)
 }
 
+/// Functions of the `datatest` crate loading test cases from the file.
+const DATA_LOADERS: &[&str] = &["json", "jsonl", "toml", "csv", "yaml", "yaml_documents"];
+
+/// If expression is a call of the `datatest` loader with a literal path (like
+/// `datatest::yaml_documents("tests/cases.yaml")`), return the loader and the path, so the path is
+/// resolved and tracked the same way as the path given directly.
+fn loader_call(expr: &syn::Expr) -> Option<(Ident, syn::LitStr)> {
+    let call = match expr {
+        syn::Expr::Call(call) if call.args.len() == 1 => call,
+        _ => return None,
+    };
+    let path = match call.func.as_ref() {
+        syn::Expr::Path(syn::ExprPath {
+            qself: None, path, ..
+        }) => path,
+        _ => return None,
+    };
+    let segments = path.segments.iter().collect::<Vec<_>>();
+    let loader = match segments.as_slice() {
+        [krate, loader]
+            if krate.ident == "datatest"
+                && krate.arguments.is_empty()
+                && loader.arguments.is_empty()
+                && DATA_LOADERS.iter().any(|name| loader.ident == name) =>
+        {
+            loader.ident.clone()
+        }
+        _ => return None,
+    };
+    match &call.args[0] {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Str(path),
+            ..
+        }) => Some((loader, path.clone())),
+        _ => None,
+    }
+}
+
+/// Choose the function loading test cases based on the extension of the data file.
+fn data_loader(path: &str) -> Ident {
+    let loader = if path.ends_with(".json") {
+        "json"
+    } else if path.ends_with(".jsonl") || path.ends_with(".ndjson") {
+        "jsonl"
+    } else if path.ends_with(".toml") {
+        "toml"
+    } else if path.ends_with(".csv") || path.ends_with(".tsv") {
+        "csv"
+    } else {
+        "yaml"
+    };
+    Ident::new(loader, Span::call_site())
+}
+
 fn data_internal(
     args: proc_macro::TokenStream,
     func: proc_macro::TokenStream,
     channel: Registration,
 ) -> proc_macro::TokenStream {
     let mut func_item = parse_macro_input!(func as ItemFn);
-    let cases: DataTestArgs = parse_macro_input!(args as DataTestArgs);
-    let info = handle_common_attrs(&mut func_item, false);
-    let cases = match cases {
-        DataTestArgs::Literal(path) => quote!(datatest::yaml(#path)),
-        DataTestArgs::Expression(expr) => quote!(#expr),
+    let args: DataTestArgs = parse_macro_input!(args as DataTestArgs);
+    let info = handle_common_attrs(&mut func_item);
+    let source = match args.cases {
+        DataTestCases::Literal(path) => Ok((None, path)),
+        DataTestCases::Expression(expr) => loader_call(&expr)
+            .map(|(loader, path)| (Some(loader), path))
+            .ok_or(expr),
+    };
+    let (cases, tracking) = match source {
+        Ok((loader, path)) => {
+            let resolved = match DataPath::resolve(&path.value()) {
+                Ok(resolved) => resolved,
+                Err(err) => return Error::new(path.span(), err).to_compile_error().into(),
+            };
+            let full_path = resolved.full_path_expr();
+            let loader = loader.unwrap_or_else(|| data_loader(&resolved.relative));
+            (
+                quote!(datatest::#loader(#full_path)),
+                track::track(&resolved),
+            )
+        }
+        // Cannot tell which files custom expression depends on
+        Err(expr) => (quote!(#expr), TokenStream::new()),
+    };
+    let xfail = match resolve_optional(args.xfail.as_ref()) {
+        Ok(xfail) => xfail,
+        Err(err) => return err.to_compile_error().into(),
     };
     let func_ident = &func_item.sig.ident;
 
@@ -487,6 +988,7 @@ fn data_internal(
     );
 
     let ignore = info.ignore;
+    let should_panic = info.should_panic.to_tokens();
     // FIXME: check file exists!
     let mut args = func_item.sig.inputs.iter();
 
@@ -522,13 +1024,17 @@ fn data_internal(
 
     let registration = test_registration(channel, &desc_ident);
     let output = quote! {
+        #tracking
+
         #registration
         #[automatically_derived]
         #[allow(non_upper_case_globals)]
         static #desc_ident: ::datatest::__internal::DataTestDesc = ::datatest::__internal::DataTestDesc {
             name: concat!(module_path!(), "::", #func_name_str),
             ignore: #ignore,
+            should_panic: #should_panic,
             describefn: #describe_func_ident,
+            xfail: #xfail,
             source_file: file!(),
         };
 
@@ -542,19 +1048,23 @@ fn data_internal(
         #[automatically_derived]
         #[allow(non_snake_case)]
         fn #describe_func_ident() -> Vec<::datatest::DataTestCaseDesc<::datatest::__internal::DataTestFn>> {
-            let result = #cases
+            let cases: Vec<::datatest::DataTestCaseDesc<Result<#ty, String>>> =
+                ::datatest::__internal::DataTestCases::into_cases(#cases);
+            cases
                 .into_iter()
                 .map(|input| {
-                    let case = input.case;
+                    let case = match input.case {
+                        Ok(case) => #case_ctor,
+                        // Test case which cannot be loaded is reported as a failing test
+                        Err(error) => ::datatest::__internal::DataTestFn::TestFn(Box::new(move || panic!("{}", error))),
+                    };
                     ::datatest::DataTestCaseDesc {
-                        case: #case_ctor,
+                        case,
                         name: input.name,
                         location: input.location,
                     }
                 })
-                .collect::<Vec<_>>();
-            assert!(!result.is_empty(), "no test cases were found!");
-            result
+                .collect::<Vec<_>>()
         }
 
         #func_item
@@ -612,19 +1122,13 @@ pub fn test_ctor_registration(
     func: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
     let mut func_item = parse_macro_input!(func as ItemFn);
-    let info = handle_common_attrs(&mut func_item, true);
+    let info = handle_common_attrs(&mut func_item);
     let func_ident = &func_item.sig.ident;
     let func_name_str = func_ident.to_string();
     let desc_ident = Ident::new(&format!("__TEST_{}", func_ident), func_ident.span());
 
     let ignore = info.ignore;
-    let should_panic = match info.should_panic {
-        ShouldPanic::No => quote!(::datatest::__internal::RegularShouldPanic::No),
-        ShouldPanic::Yes => quote!(::datatest::__internal::RegularShouldPanic::Yes),
-        ShouldPanic::YesWithMessage(v) => {
-            quote!(::datatest::__internal::RegularShouldPanic::YesWithMessage(#v))
-        }
-    };
+    let should_panic = info.should_panic.to_tokens();
     let registration = test_registration(Registration::Ctor, &desc_ident);
     let output = quote! {
         #registration
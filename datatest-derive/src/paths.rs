@@ -0,0 +1,109 @@
+//! Resolving paths to the test data given in the attributes. Paths are relative to the root of the
+//! crate with tests (`CARGO_MANIFEST_DIR`) or, if prefixed with `workspace:`, to the root of the
+//! workspace, so tests do not depend on the current directory. Absolute paths are used as is.
+use proc_macro2::TokenStream;
+use quote::quote;
+use std::path::{Component, Path, PathBuf};
+
+pub const WORKSPACE_PREFIX: &str = "workspace:";
+
+/// Path to the test data resolved at compile time.
+pub struct DataPath {
+    /// Expression giving the base directory (either crate root or workspace root)
+    pub base: TokenStream,
+    /// Path relative to the base directory, as given in the attribute (without prefix)
+    pub relative: String,
+    /// Full path, if we know where the crate root is
+    pub full: Option<PathBuf>,
+    /// Full path to the base directory, if we know where the crate root is
+    pub base_dir: Option<PathBuf>,
+}
+
+impl DataPath {
+    pub fn resolve(path: &str) -> Result<DataPath, String> {
+        let manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR").map(PathBuf::from);
+        let (relative, workspace) = match path.strip_prefix(WORKSPACE_PREFIX) {
+            Some(relative) => (relative, true),
+            None => (path, false),
+        };
+
+        if Path::new(relative).is_absolute() {
+            return Ok(DataPath {
+                base: quote!(env!("CARGO_MANIFEST_DIR")),
+                relative: relative.to_string(),
+                full: Some(PathBuf::from(relative)),
+                base_dir: manifest_dir,
+            });
+        }
+        if !workspace {
+            return Ok(DataPath {
+                base: quote!(env!("CARGO_MANIFEST_DIR")),
+                relative: relative.to_string(),
+                full: manifest_dir.as_ref().map(|dir| dir.join(relative)),
+                base_dir: manifest_dir,
+            });
+        }
+
+        let manifest_dir =
+            manifest_dir.ok_or("cannot find workspace root: `CARGO_MANIFEST_DIR` is not set")?;
+        let levels = workspace_levels(&manifest_dir).ok_or_else(|| {
+            format!(
+                "cannot find workspace root for the crate at `{}`",
+                manifest_dir.display()
+            )
+        })?;
+        // Keep base relative to the crate root, so it is the same as `env!("CARGO_MANIFEST_DIR")`
+        let parents = "/..".repeat(levels);
+        let base_dir = manifest_dir.ancestors().nth(levels).map(Path::to_path_buf);
+        Ok(DataPath {
+            base: quote!(concat!(env!("CARGO_MANIFEST_DIR"), #parents)),
+            relative: relative.to_string(),
+            full: base_dir.as_ref().map(|dir| dir.join(relative)),
+            base_dir,
+        })
+    }
+
+    /// Expression giving the full path to the test data.
+    pub fn full_path_expr(&self) -> TokenStream {
+        let base = &self.base;
+        let relative = &self.relative;
+        if Path::new(relative).is_absolute() {
+            return quote!(#relative);
+        }
+        quote!(concat!(#base, "/", #relative))
+    }
+}
+
+/// Find the root of the workspace (starting from the crate root) and give the amount of levels to
+/// go up to it. Crate could point to the root explicitly via `package.workspace`, otherwise it is
+/// the nearest directory with `Cargo.toml` which has a `[workspace]` table.
+fn workspace_levels(manifest_dir: &Path) -> Option<usize> {
+    let manifest = read_manifest(manifest_dir);
+    let explicit = manifest
+        .as_ref()
+        .and_then(|manifest| manifest.get("package"))
+        .and_then(|package| package.get("workspace"))
+        .and_then(|workspace| workspace.as_str());
+    if let Some(workspace) = explicit {
+        // Only parent directories could be reached by going up from the crate root
+        let components = Path::new(workspace).components().collect::<Vec<_>>();
+        return components
+            .iter()
+            .all(|c| matches!(c, Component::ParentDir | Component::CurDir))
+            .then(|| {
+                components
+                    .iter()
+                    .filter(|c| matches!(c, Component::ParentDir))
+                    .count()
+            });
+    }
+    manifest_dir.ancestors().position(|dir| {
+        read_manifest(dir).is_some_and(|manifest| manifest.contains_key("workspace"))
+    })
+}
+
+/// Read and parse `Cargo.toml` in the given directory.
+fn read_manifest(dir: &Path) -> Option<toml::Table> {
+    let manifest = std::fs::read_to_string(dir.join("Cargo.toml")).ok()?;
+    manifest.parse::<toml::Table>().ok()
+}
@@ -0,0 +1,215 @@
+//! Tell the compiler which data files the test depends on, so the test binary is rebuilt when
+//! they change.
+//!
+//! On nightly, paths are registered via `proc_macro::tracked::path`, which also works for
+//! directories (so adding or removing files triggers a rebuild, too). Otherwise, we emit an
+//! `include_bytes!` for every data file, which only tracks changes of the existing files. To keep
+//! the test binary small, only files the test could use are tracked this way (files matched by
+//! the pattern and files derived from them via templates) and files larger than
+//! `MAX_TRACKED_SIZE` are not tracked at all.
+use crate::paths::DataPath;
+use datatest_internals::glob;
+use proc_macro2::TokenStream;
+use regex::Regex;
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+/// Files larger than this are not embedded into the test binary to track their changes on stable.
+#[cfg_attr(feature = "rustc_has_tracked_path", allow(dead_code))]
+const MAX_TRACKED_SIZE: u64 = 1024 * 1024;
+
+/// Arguments of the `#[datatest::files(..)]` test which define the files it uses.
+pub struct FilesFilter<'a> {
+    pub pattern: &'a str,
+    pub glob: bool,
+    /// Templates of the files derived from the matched one
+    pub templates: Vec<&'a str>,
+    pub directories: bool,
+    pub exclude: Vec<String>,
+    pub max_depth: Option<usize>,
+    pub include_hidden: bool,
+    pub prune_hidden: bool,
+}
+
+/// Track data file.
+pub fn track(path: &DataPath) -> TokenStream {
+    match path.full {
+        Some(ref full) => track_paths(std::slice::from_ref(full)),
+        None => TokenStream::new(),
+    }
+}
+
+/// Track files in the test directory used by the `#[datatest::files(..)]` test.
+pub fn track_files(root: &DataPath, filter: &FilesFilter) -> TokenStream {
+    let (full, base_dir) = match (&root.full, &root.base_dir) {
+        (Some(full), Some(base_dir)) => (full, base_dir),
+        _ => return TokenStream::new(),
+    };
+    if datatest_internals::is_archive(&root.relative) {
+        return track_paths(std::slice::from_ref(full));
+    }
+    // Invalid patterns are reported elsewhere
+    let scanner = match Scanner::new(root, full, base_dir, filter) {
+        Ok(scanner) => scanner,
+        Err(_) => return TokenStream::new(),
+    };
+    let mut paths = BTreeSet::from([full.clone()]);
+    scanner.scan(full, 1, &mut paths);
+    track_paths(&paths)
+}
+
+/// Mirrors how the test runner looks for the test cases.
+struct Scanner<'a> {
+    root: &'a DataPath,
+    full: &'a Path,
+    base_dir: &'a Path,
+    filter: &'a FilesFilter<'a>,
+    regex: Regex,
+    templates: Vec<Cow<'a, str>>,
+    exclude: Vec<Regex>,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(
+        root: &'a DataPath,
+        full: &'a Path,
+        base_dir: &'a Path,
+        filter: &'a FilesFilter<'a>,
+    ) -> Result<Scanner<'a>, String> {
+        let (regex, templates) = if filter.glob {
+            let templates = filter
+                .templates
+                .iter()
+                .map(|template| glob::translate_template(template))
+                .collect::<Result<Vec<_>, _>>()?;
+            (glob::translate(filter.pattern)?, templates)
+        } else {
+            let templates = filter.templates.iter().map(|t| Cow::Borrowed(*t)).collect();
+            (filter.pattern.to_string(), templates)
+        };
+        let exclude = filter
+            .exclude
+            .iter()
+            .map(|pattern| glob::translate(pattern).and_then(|re| compile(&re)))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Scanner {
+            root,
+            full,
+            base_dir,
+            filter,
+            regex: compile(&regex)?,
+            templates,
+            exclude,
+        })
+    }
+
+    /// Collect directories inside the given one, files matched by the pattern and files derived
+    /// from them. Symbolic links to directories are not followed, so we never loop.
+    fn scan(&self, dir: &Path, depth: usize, paths: &mut BTreeSet<PathBuf>) {
+        if self
+            .filter
+            .max_depth
+            .is_some_and(|max_depth| depth > max_depth)
+        {
+            return;
+        }
+        let mut entries = match std::fs::read_dir(dir) {
+            Ok(entries) => entries.flatten().map(|e| e.path()).collect::<Vec<_>>(),
+            Err(_) => return,
+        };
+        entries.sort();
+        for entry in entries {
+            let relative = entry.strip_prefix(self.full).unwrap_or(&entry);
+            let glob_path = glob::to_glob_path(relative);
+            if self.exclude.iter().any(|re| re.is_match(&glob_path)) {
+                continue;
+            }
+            let is_hidden = entry
+                .file_name()
+                .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+            let is_hidden = is_hidden && !self.filter.include_hidden;
+            let is_dir = entry
+                .symlink_metadata()
+                .is_ok_and(|meta| meta.file_type().is_dir());
+            if is_dir {
+                if is_hidden && self.filter.prune_hidden {
+                    continue;
+                }
+                paths.insert(entry.clone());
+                if self.filter.directories && !is_hidden {
+                    self.check_match(&entry, relative, &glob_path, paths);
+                }
+                self.scan(&entry, depth + 1, paths);
+            } else if !self.filter.directories && !is_hidden {
+                self.check_match(&entry, relative, &glob_path, paths);
+            }
+        }
+    }
+
+    fn check_match(
+        &self,
+        path: &Path,
+        relative: &Path,
+        glob_path: &str,
+        paths: &mut BTreeSet<PathBuf>,
+    ) {
+        // Same as in the test runner: glob patterns are matched against the path relative to the
+        // root, regular expressions against the path relative to the base directory
+        let input_path = if self.filter.glob {
+            Cow::Borrowed(glob_path)
+        } else {
+            Path::new(&self.root.relative)
+                .join(relative)
+                .to_string_lossy()
+                .into_owned()
+                .into()
+        };
+        if !self.regex.is_match(&input_path) {
+            return;
+        }
+        if path.is_file() {
+            paths.insert(path.to_path_buf());
+        }
+        for template in &self.templates {
+            let rendered = self.regex.replace_all(&input_path, template.as_ref());
+            let rendered = if self.filter.glob {
+                self.full.join(rendered.as_ref())
+            } else {
+                self.base_dir.join(rendered.as_ref())
+            };
+            if rendered.is_file() {
+                paths.insert(rendered);
+            }
+        }
+    }
+}
+
+fn compile(regex: &str) -> Result<Regex, String> {
+    Regex::new(regex).map_err(|e| e.to_string())
+}
+
+#[cfg(feature = "rustc_has_tracked_path")]
+fn track_paths<'a>(paths: impl IntoIterator<Item = &'a PathBuf>) -> TokenStream {
+    for path in paths {
+        proc_macro::tracked::path(path);
+    }
+    TokenStream::new()
+}
+
+#[cfg(not(feature = "rustc_has_tracked_path"))]
+fn track_paths<'a>(paths: impl IntoIterator<Item = &'a PathBuf>) -> TokenStream {
+    let files = paths
+        .into_iter()
+        .filter(|path| {
+            std::fs::metadata(path)
+                .is_ok_and(|meta| meta.is_file() && meta.len() <= MAX_TRACKED_SIZE)
+        })
+        .map(|path| path.to_string_lossy().into_owned());
+    quote::quote! {
+        #(
+            #[automatically_derived]
+            const _: &[u8] = include_bytes!(#files);
+        )*
+    }
+}
@@ -0,0 +1,124 @@
+//! Compile-time checks of the `#[datatest::files(..)]` attribute arguments. Catching these early
+//! gives an error pointing to the attribute instead of a panic when running the test binary.
+use datatest_internals::glob;
+use regex::Regex;
+use std::path::Path;
+
+/// Check that test directory (or archive) exists. `root` is the path as given in the attribute,
+/// `path` is where it resolves to.
+pub fn check_root(root: &str, path: &Path) -> Result<(), String> {
+    let (kind, exists) = if datatest_internals::is_archive(root) {
+        ("archive", path.is_file())
+    } else {
+        ("directory", path.is_dir())
+    };
+    if exists {
+        return Ok(());
+    }
+    // Full path depends on where the crate is checked out, so only say what it is relative to
+    let base = if Path::new(root).is_absolute() {
+        ""
+    } else if root.starts_with(crate::paths::WORKSPACE_PREFIX) {
+        " (relative to the workspace root)"
+    } else {
+        " (relative to the crate root)"
+    };
+    Err(format!("test {} `{}` does not exist{}", kind, root, base))
+}
+
+/// Capture groups defined by the pattern, which templates could refer to.
+pub struct Captures {
+    /// Amount of capture groups, including the implicit group `0` (the whole match)
+    len: usize,
+    names: Vec<String>,
+}
+
+impl Captures {
+    /// Collect capture groups of the regular expression pattern (`<arg> in "<regex>"`).
+    pub fn from_regex(pattern: &str) -> Result<Captures, String> {
+        let regex = Regex::new(pattern).map_err(|e| e.to_string())?;
+        Ok(Captures {
+            len: regex.captures_len(),
+            names: regex.capture_names().flatten().map(String::from).collect(),
+        })
+    }
+
+    /// Collect capture groups of the glob pattern (`<arg> glob "<glob>"`). Every wildcard is a
+    /// capture group, `{name}` wildcards are named ones.
+    pub fn from_glob(pattern: &str) -> Result<Captures, String> {
+        Captures::from_regex(&glob::translate(pattern)?)
+    }
+
+    /// Check template used with the regular expression pattern. Uses the syntax of the
+    /// replacement string from the `regex` crate (`$name`, `${name}`, `$1`, `${1}` and `$$`).
+    pub fn check_regex_template(&self, template: &str) -> Result<(), String> {
+        self.check_replacement(template, template)
+    }
+
+    /// Check template used with the glob pattern (`{name}`, `{1}`).
+    pub fn check_glob_template(&self, template: &str) -> Result<(), String> {
+        self.check_replacement(&glob::translate_template(template)?, template)
+    }
+
+    /// Check references of the replacement string; errors mention the template it came from.
+    fn check_replacement(&self, replacement: &str, template: &str) -> Result<(), String> {
+        let mut rest = replacement;
+        while let Some(pos) = rest.find('$') {
+            rest = &rest[pos + 1..];
+            let name = if let Some(braced) = rest.strip_prefix('{') {
+                match braced.find('}') {
+                    Some(end) => {
+                        rest = &braced[end + 1..];
+                        &braced[..end]
+                    }
+                    // Not a reference, `$` is taken literally
+                    None => continue,
+                }
+            } else if let Some(escaped) = rest.strip_prefix('$') {
+                rest = escaped;
+                continue;
+            } else {
+                let end = rest
+                    .find(|ch: char| ch != '_' && !ch.is_ascii_alphanumeric())
+                    .unwrap_or(rest.len());
+                let name = &rest[..end];
+                rest = &rest[end..];
+                name
+            };
+            if !name.is_empty() {
+                self.check_reference(name, template)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check capture group bound to an argument (`<arg> = capture "<name>"`).
+    pub fn check_capture(&self, name: &str) -> Result<(), String> {
+        if self.is_defined(name) {
+            Ok(())
+        } else {
+            Err(format!(
+                "capture group `{}` is not defined by the pattern",
+                name
+            ))
+        }
+    }
+
+    fn is_defined(&self, name: &str) -> bool {
+        match name.parse::<usize>() {
+            Ok(index) => index < self.len,
+            Err(_) => self.names.iter().any(|n| n == name),
+        }
+    }
+
+    fn check_reference(&self, name: &str, template: &str) -> Result<(), String> {
+        if self.is_defined(name) {
+            Ok(())
+        } else {
+            Err(format!(
+                "template '{}' refers to capture group `{}` which is not defined by the pattern",
+                template, name
+            ))
+        }
+    }
+}
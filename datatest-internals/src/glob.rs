@@ -0,0 +1,163 @@
+//! Translation of glob patterns (`<arg> glob "<pattern>"`) and their templates into regular
+//! expressions and replacement strings of the `regex` crate.
+//!
+//! The following syntax is supported:
+//!
+//! * `*` matches any sequence of characters except `/`
+//! * `?` matches any single character except `/`
+//! * `[...]`, `[!...]` match any character from the given set (or any character not in the set)
+//! * `**` (as a whole path segment) matches any number of directories, including none
+//! * `{name}` is the same as `*`, but the capture group gets a name
+use std::borrow::Cow;
+use std::path::{Component, Path};
+
+/// Translate glob pattern into a regular expression matching the whole path, where each wildcard
+/// becomes a capture group.
+pub fn translate(pattern: &str) -> Result<String, String> {
+    let parts = pattern.split('/').collect::<Vec<_>>();
+    let mut source = String::from("^");
+    for (idx, part) in parts.iter().enumerate() {
+        let is_last = idx + 1 == parts.len();
+        if *part == "**" {
+            // Include trailing slash into the capture, so `**/*.txt` matches `a.txt`, too
+            source += if is_last { "(.*)" } else { "((?:[^/]+/)*)" };
+        } else {
+            source += &translate_segment(part, true)?;
+            if !is_last {
+                source.push('/');
+            }
+        }
+    }
+    source.push('$');
+    Ok(source)
+}
+
+/// Translate one path segment of the glob into a regular expression. If `capture` is `false`,
+/// wildcards are translated into non-capturing groups.
+pub fn translate_segment(segment: &str, capture: bool) -> Result<String, String> {
+    let group = |name: Option<&str>, re: &str| match (capture, name) {
+        (false, _) => format!("(?:{})", re),
+        (true, None) => format!("({})", re),
+        (true, Some(name)) => format!("(?P<{}>{})", name, re),
+    };
+
+    let mut out = String::new();
+    let mut chars = segment.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '*' => out += &group(None, "[^/]*"),
+            '?' => out += &group(None, "[^/]"),
+            '[' => {
+                let mut class = String::from("[");
+                let mut first = true;
+                loop {
+                    match chars.next() {
+                        Some('!') if first => class.push('^'),
+                        Some(']') if !first => break,
+                        Some('\\') => class += "\\\\",
+                        Some('[') => class += "\\[",
+                        Some(ch) => class.push(ch),
+                        None => return Err(format!("unclosed '[' in '{}'", segment)),
+                    }
+                    first = false;
+                }
+                class.push(']');
+                out += &group(None, &class);
+            }
+            '{' => {
+                let name = take_name(&mut chars)
+                    .ok_or_else(|| format!("unclosed '{{' in '{}'", segment))?;
+                if !is_identifier(&name) {
+                    return Err(format!("invalid wildcard name '{}' in '{}'", name, segment));
+                }
+                out += &group(Some(&name), "[^/]*");
+            }
+            ch => out += &regex::escape(ch.encode_utf8(&mut [0; 4])),
+        }
+    }
+    Ok(out)
+}
+
+/// Translate template used together with the glob pattern into the replacement string for the
+/// regular expression. Templates reference captures via `{name}` (named wildcards) or `{1}`, `{2}`,
+/// etc (all wildcards, in order).
+pub fn translate_template(template: &str) -> Result<Cow<'_, str>, String> {
+    if !template.contains(['{', '$']) {
+        return Ok(Cow::Borrowed(template));
+    }
+
+    let mut out = String::new();
+    let mut chars = template.chars();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '{' => {
+                let name = take_name(&mut chars)
+                    .ok_or_else(|| format!("unclosed '{{' in template '{}'", template))?;
+                let is_index = !name.is_empty() && name.chars().all(|ch| ch.is_ascii_digit());
+                if !is_index && !is_identifier(&name) {
+                    return Err(format!(
+                        "invalid capture '{}' in template '{}'",
+                        name, template
+                    ));
+                }
+                out += "${";
+                out += &name;
+                out.push('}');
+            }
+            '$' => out += "$$",
+            ch => out.push(ch),
+        }
+    }
+    Ok(Cow::Owned(out))
+}
+
+/// Escape wildcards in the text (by putting each of them into a set of its own, like `[*]`), so
+/// the glob matches the text as is.
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '*' | '?' | '[' | ']' | '{' | '}' => {
+                out.push('[');
+                out.push(ch);
+                out.push(']');
+            }
+            ch => out.push(ch),
+        }
+    }
+    out
+}
+
+/// Convert relative path into a string with `/` as a separator (used for matching globs).
+pub fn to_glob_path(path: &Path) -> String {
+    let mut out = String::new();
+    for component in path.components() {
+        if let Component::Normal(name) = component {
+            if !out.is_empty() {
+                out.push('/');
+            }
+            out += &name.to_string_lossy();
+        }
+    }
+    out
+}
+
+/// Take everything up to the closing `}`; `None` if there is no closing brace.
+fn take_name(chars: &mut std::str::Chars) -> Option<String> {
+    let mut name = String::new();
+    for ch in chars {
+        if ch == '}' {
+            return Some(name);
+        }
+        name.push(ch);
+    }
+    None
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|ch| ch == '_' || ch.is_ascii_alphabetic())
+        && chars.all(|ch| ch == '_' || ch.is_ascii_alphanumeric())
+}
@@ -0,0 +1,11 @@
+//! Helpers shared by the `datatest` crate and the `datatest-derive` procedural macros, so patterns
+//! checked at compile time are interpreted exactly the same way when tests run.
+pub mod glob;
+
+/// Extensions of the archives which could be used as the test directory.
+pub const ARCHIVE_EXTENSIONS: &[&str] = &[".tar", ".tar.gz", ".tgz", ".zip"];
+
+/// Check if the path looks like an archive we support.
+pub fn is_archive(path: &str) -> bool {
+    ARCHIVE_EXTENSIONS.iter().any(|ext| path.ends_with(ext))
+}
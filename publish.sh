@@ -18,6 +18,10 @@ git tag --annotate --message "releasing version ${VER}" "v${VER}"
 git push --tags
 popd
 
+pushd "${DIR}/datatest-internals"
+cargo publish
+popd
+
 pushd "${DIR}/datatest-derive"
 cargo publish
 popd
@@ -0,0 +1,214 @@
+//! Support for archives (`.tar`, `.tar.gz`, `.tgz` and `.zip`) used as the root of
+//! `#[datatest::files(..)]` tests.
+//!
+//! Archive is read into memory once, when looking for test cases. Entries are given to the test
+//! function as paths inside the archive path (like `tests/corpus.tar.gz/cases/a.txt`) and their
+//! contents are read from memory rather than from the file system.
+use crate::files::FilesTestDesc;
+use crate::glob::Glob;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Cursor, Read};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// Archives loaded so far, by their path.
+static ARCHIVES: Mutex<Vec<(PathBuf, Arc<Archive>)>> = Mutex::new(Vec::new());
+
+/// Contents of the archive, with paths relative to the archive root.
+pub(crate) struct Archive {
+    files: BTreeMap<PathBuf, Arc<[u8]>>,
+    /// Directories, including the ones which are only implied by the paths of the files
+    directories: BTreeSet<PathBuf>,
+}
+
+/// Check if the path looks like an archive we support.
+pub(crate) fn is_archive(path: &Path) -> bool {
+    datatest_internals::is_archive(&path.to_string_lossy())
+}
+
+/// Load archive at the given path (or give the one loaded before).
+pub(crate) fn load(path: &Path) -> Arc<Archive> {
+    let find = |archives: &[(PathBuf, Arc<Archive>)]| {
+        archives
+            .iter()
+            .find(|(p, _)| p == path)
+            .map(|(_, archive)| archive.clone())
+    };
+    if let Some(archive) = find(&archives()) {
+        return archive;
+    }
+    // Read the archive without holding the lock, so we don't poison it if we fail
+    let archive = Archive::read(path)
+        .unwrap_or_else(|e| panic!("cannot read archive '{}': {}", path.display(), e));
+    let mut archives = archives();
+    // Could be loaded by another thread in the meantime
+    if let Some(archive) = find(&archives) {
+        return archive;
+    }
+    let archive = Arc::new(archive);
+    archives.push((path.to_path_buf(), archive.clone()));
+    archive
+}
+
+/// Lock the list of loaded archives. The list is always consistent, so it is fine to use it even
+/// if some thread panicked while holding the lock.
+fn archives() -> MutexGuard<'static, Vec<(PathBuf, Arc<Archive>)>> {
+    ARCHIVES.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Find the archive entry for the path inside one of the loaded archives.
+fn lookup(path: &Path) -> Option<(Arc<Archive>, PathBuf)> {
+    let archives = archives();
+    archives.iter().find_map(|(root, archive)| {
+        let relative = path.strip_prefix(root).ok()?;
+        Some((archive.clone(), relative.to_path_buf()))
+    })
+}
+
+/// Open file for reading, either from the file system or from the archive.
+pub(crate) fn open(path: &Path) -> io::Result<Box<dyn BufRead>> {
+    match lookup(path) {
+        Some((archive, relative)) => match archive.files.get(&relative) {
+            Some(contents) => Ok(Box::new(Cursor::new(contents.clone()))),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no file '{}' in the archive", relative.display()),
+            )),
+        },
+        None => Ok(Box::new(BufReader::new(File::open(path)?))),
+    }
+}
+
+/// Check if file exists, either in the file system or in the archive.
+pub(crate) fn exists(path: &Path) -> bool {
+    match lookup(path) {
+        Some((archive, relative)) => {
+            archive.files.contains_key(&relative) || archive.directories.contains(&relative)
+        }
+        None => path.exists(),
+    }
+}
+
+impl Archive {
+    fn read(path: &Path) -> Result<Archive, Box<dyn std::error::Error>> {
+        let mut archive = Archive {
+            files: BTreeMap::new(),
+            directories: BTreeSet::new(),
+        };
+        let file = BufReader::new(File::open(path)?);
+        let name = path.to_string_lossy();
+        if name.ends_with(".zip") {
+            let mut zip = zip::ZipArchive::new(file)?;
+            for idx in 0..zip.len() {
+                let mut entry = zip.by_index(idx)?;
+                let entry_path = match entry.enclosed_name() {
+                    Some(entry_path) => entry_path.to_path_buf(),
+                    None => return Err(format!("invalid path '{}'", entry.name()).into()),
+                };
+                if entry.is_dir() {
+                    archive.add_directory(&entry_path);
+                } else {
+                    let mut contents = Vec::new();
+                    entry.read_to_end(&mut contents)?;
+                    archive.add_file(&entry_path, contents);
+                }
+            }
+        } else if name.ends_with(".tar") {
+            archive.read_tar(file)?;
+        } else {
+            archive.read_tar(flate2::read::GzDecoder::new(file))?;
+        }
+        Ok(archive)
+    }
+
+    fn read_tar(&mut self, reader: impl Read) -> io::Result<()> {
+        let mut tar = tar::Archive::new(reader);
+        for entry in tar.entries()? {
+            let mut entry = entry?;
+            let entry_path = normalize(&entry.path()?);
+            let kind = entry.header().entry_type();
+            if kind.is_dir() {
+                self.add_directory(&entry_path);
+            } else if kind.is_file() {
+                let mut contents = Vec::new();
+                entry.read_to_end(&mut contents)?;
+                self.add_file(&entry_path, contents);
+            }
+            // Links and special files are skipped
+        }
+        Ok(())
+    }
+
+    fn add_file(&mut self, path: &Path, contents: Vec<u8>) {
+        if let Some(parent) = path.parent() {
+            self.add_directory(parent);
+        }
+        self.files.insert(path.to_path_buf(), contents.into());
+    }
+
+    fn add_directory(&mut self, path: &Path) {
+        for dir in path.ancestors() {
+            if dir.as_os_str().is_empty() || !self.directories.insert(dir.to_path_buf()) {
+                break;
+            }
+        }
+    }
+
+    /// Same as [`crate::walk::iterate_directory`], but for the archive entries. Paths are given
+    /// inside the archive path `root`. Ignore files and symbolic links are not supported, and
+    /// entries are always sorted by name.
+    pub fn iterate(&self, root: &Path, desc: &FilesTestDesc) -> Vec<PathBuf> {
+        let exclude = desc
+            .exclude
+            .iter()
+            .map(|pattern| {
+                Glob::new(pattern)
+                    .unwrap_or_else(|e| panic!("invalid exclude pattern '{}': {}", pattern, e))
+            })
+            .collect::<Vec<_>>();
+        let entries: Box<dyn Iterator<Item = &PathBuf>> = if desc.directories {
+            Box::new(self.directories.iter())
+        } else {
+            Box::new(self.files.keys())
+        };
+
+        entries
+            .filter(|path| {
+                let depth = path.components().count();
+                if desc.max_depth.is_some_and(|max_depth| depth > max_depth) {
+                    return false;
+                }
+                // Entry is skipped if it is hidden, or if it or any of its parents is excluded (or
+                // hidden, if hidden directories are pruned)
+                let prune_hidden = desc.prune_hidden && !desc.include_hidden;
+                path.ancestors()
+                    .filter(|dir| !dir.as_os_str().is_empty())
+                    .enumerate()
+                    .all(|(idx, dir)| {
+                        let is_hidden = dir
+                            .file_name()
+                            .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+                        let skip_hidden = if idx == 0 {
+                            !desc.include_hidden
+                        } else {
+                            prune_hidden
+                        };
+                        if is_hidden && skip_hidden {
+                            return false;
+                        }
+                        let dir_str = crate::glob::to_glob_path(dir);
+                        !exclude.iter().any(|glob| glob.regex().is_match(&dir_str))
+                    })
+            })
+            .map(|path| root.join(path))
+            .collect()
+    }
+}
+
+/// Drop `.` components of the path inside the archive (tar archives often have them).
+fn normalize(path: &Path) -> PathBuf {
+    path.components()
+        .filter(|component| matches!(component, Component::Normal(_)))
+        .collect()
+}
@@ -1,8 +1,16 @@
 //! Support module for `#[datatest::data(..)]`
+use crate::runner::RegularShouldPanic;
 use rustc_test::Bencher;
 #[cfg(feature = "rustc_test_TDynBenchFn")]
 use rustc_test::TDynBenchFn;
-use serde::de::DeserializeOwned;
+use serde::de::{DeserializeOwned, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use serde_json::value::RawValue;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::marker::PhantomData;
 use std::path::Path;
 use yaml_rust::parser::Event;
 use yaml_rust::scanner::Marker;
@@ -12,7 +20,11 @@ use yaml_rust::scanner::Marker;
 pub struct DataTestDesc {
     pub name: &'static str,
     pub ignore: bool,
+    /// Set by `#[should_panic]` on the test function, applies to every test case
+    pub should_panic: RegularShouldPanic,
     pub describefn: fn() -> Vec<DataTestCaseDesc<DataTestFn>>,
+    /// Path to the manifest listing test cases which are expected to fail
+    pub xfail: Option<&'static str>,
     pub source_file: &'static str,
 }
 
@@ -31,31 +43,450 @@ pub enum DataTestFn {
 /// Descriptor of the data test case where the type of the test case data is `T`.
 pub struct DataTestCaseDesc<T> {
     pub case: T,
+    /// Name of the test case, which should not depend on its location (so test name does not
+    /// change when other test cases are added or removed)
     pub name: Option<String>,
+    /// Location of the test case in the file, like `line 3` (shown when test case fails or used
+    /// as a test case name, if there is no name)
     pub location: String,
 }
 
+/// Test cases given to `#[datatest::data(..)]`: either test cases themselves or, for the test
+/// cases which could fail to load, results where `Err` is the error message (such test case is
+/// reported as a failing test).
+#[doc(hidden)]
+pub trait DataTestCases<T> {
+    fn into_cases(self) -> Vec<DataTestCaseDesc<Result<T, String>>>;
+}
+
+impl<T> DataTestCases<T> for Vec<DataTestCaseDesc<T>> {
+    fn into_cases(self) -> Vec<DataTestCaseDesc<Result<T, String>>> {
+        self.into_iter()
+            .map(|desc| DataTestCaseDesc {
+                case: Ok(desc.case),
+                name: desc.name,
+                location: desc.location,
+            })
+            .collect()
+    }
+}
+
+impl<T> DataTestCases<T> for Vec<DataTestCaseDesc<Result<T, String>>> {
+    fn into_cases(self) -> Vec<DataTestCaseDesc<Result<T, String>>> {
+        self
+    }
+}
+
+/// Load test cases from the YAML file, either a sequence of test cases or a mapping where keys are
+/// the names of the test cases. Each test case is deserialized separately and test cases which
+/// cannot be deserialized are reported as failing tests.
 pub fn yaml<T: DeserializeOwned + TestNameWithDefault + Send + 'static>(
     path: &str,
-) -> Vec<DataTestCaseDesc<T>> {
+) -> Vec<DataTestCaseDesc<Result<T, String>>> {
     let input = std::fs::read_to_string(Path::new(path))
         .unwrap_or_else(|_| panic!("cannot read file '{}'", path));
 
-    let index = index_cases(&input);
-    let cases: Vec<T> = serde_yaml::from_str(&input).unwrap();
-    assert_eq!(index.len(), cases.len(), "index does not match test cases");
+    let index = index_cases(&input, 1)
+        .unwrap_or_else(|e| panic!("cannot parse test cases in '{}': {}", path, e));
+    let values: YamlCases<serde_yaml::Value> = serde_yaml::from_str(&input)
+        .unwrap_or_else(|e| panic!("cannot parse test cases in '{}': {}", path, e));
+    assert_eq!(
+        index.len(),
+        values.0.len(),
+        "index does not match test cases"
+    );
 
-    index
+    let cases = index
+        .into_iter()
+        .zip(values.0)
+        .map(|(entry, value)| {
+            let location = format!("line {}", entry.marker.line());
+            let identity = CaseIdentity {
+                id: entry.key.or_else(|| yaml_id(&value)),
+                contents: serde_yaml::to_string(&value).unwrap_or_default(),
+            };
+            // Malformed test case only fails itself, see `describe_cases`
+            let case = T::deserialize(value).map_err(|e| case_error(path, &location, e));
+            (case, identity, location)
+        })
+        .collect();
+    describe_cases(path, cases)
+}
+
+/// Test cases in the YAML file: either a sequence of test cases or a mapping from the test case
+/// names to test cases (names are taken from the index, see [`index_cases`]).
+struct YamlCases<T>(Vec<T>);
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for YamlCases<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct CasesVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> Visitor<'de> for CasesVisitor<T> {
+            type Value = YamlCases<T>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a sequence or a mapping of test cases")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+                let mut cases = Vec::new();
+                while let Some(case) = seq.next_element()? {
+                    cases.push(case);
+                }
+                Ok(YamlCases(cases))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut cases = Vec::new();
+                while let Some((IgnoredAny, case)) = map.next_entry()? {
+                    cases.push(case);
+                }
+                Ok(YamlCases(cases))
+            }
+        }
+
+        deserializer.deserialize_any(CasesVisitor(PhantomData))
+    }
+}
+
+/// Test cases in the TOML file (`[[case]]` tables), together with their locations. Other keys are
+/// ignored.
+struct TomlCases(Vec<::toml::Spanned<::toml::Value>>);
+
+impl<'de> Deserialize<'de> for TomlCases {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct CasesVisitor;
+
+        impl<'de> Visitor<'de> for CasesVisitor {
+            type Value = TomlCases;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a table with `[[case]]` tables")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut cases = None;
+                while let Some(key) = map.next_key::<String>()? {
+                    if key == "case" {
+                        cases = Some(map.next_value()?);
+                    } else {
+                        map.next_value::<IgnoredAny>()?;
+                    }
+                }
+                let cases = cases.ok_or_else(|| serde::de::Error::missing_field("case"))?;
+                Ok(TomlCases(cases))
+            }
+        }
+
+        deserializer.deserialize_map(CasesVisitor)
+    }
+}
+
+/// Load test cases from the YAML stream, where each document (separated by `---`) is a test case.
+/// Documents are deserialized one by one, so the whole file is never kept as a single value.
+pub fn yaml_documents<T: DeserializeOwned + TestNameWithDefault + Send + 'static>(
+    path: &str,
+) -> Vec<DataTestCaseDesc<Result<T, String>>> {
+    let input = std::fs::read_to_string(Path::new(path))
+        .unwrap_or_else(|_| panic!("cannot read file '{}'", path));
+
+    let index = index_cases(&input, 0)
+        .unwrap_or_else(|e| panic!("cannot parse test cases in '{}': {}", path, e));
+    let documents = serde_yaml::Deserializer::from_str(&input);
+
+    let cases = index
+        .into_iter()
+        .zip(documents)
+        .map(|(entry, document)| {
+            let location = format!("line {}", entry.marker.line());
+            let value = match serde_yaml::Value::deserialize(document) {
+                Ok(value) => value,
+                Err(e) => return malformed_case(case_error(path, &location, e), location),
+            };
+            let identity = CaseIdentity {
+                id: yaml_id(&value),
+                contents: serde_yaml::to_string(&value).unwrap_or_default(),
+            };
+            let case = T::deserialize(value).map_err(|e| case_error(path, &location, e));
+            (case, identity, location)
+        })
+        .collect();
+    describe_cases(path, cases)
+}
+
+/// Load test cases from the JSON Lines file, where each non-blank line is a test case. File is read
+/// line by line, so it is never kept in memory as a whole; line which is not valid JSON only fails
+/// its own test.
+pub fn jsonl<T: DeserializeOwned + TestNameWithDefault + Send + 'static>(
+    path: &str,
+) -> Vec<DataTestCaseDesc<Result<T, String>>> {
+    let file =
+        File::open(Path::new(path)).unwrap_or_else(|_| panic!("cannot read file '{}'", path));
+
+    let mut cases = Vec::new();
+    for (idx, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.unwrap_or_else(|e| panic!("cannot read file '{}': {}", path, e));
+        if line.trim().is_empty() {
+            continue;
+        }
+        let location = format!("line {}", idx + 1);
+        let value: serde_json::Value = match serde_json::from_str(&line) {
+            Ok(value) => value,
+            Err(e) => {
+                cases.push(malformed_case(case_error(path, &location, e), location));
+                continue;
+            }
+        };
+        let identity = CaseIdentity {
+            id: json_id(&value),
+            contents: value.to_string(),
+        };
+        // Parse the text again, so duplicate keys are rejected and errors point to the column
+        let case = serde_json::from_str(&line).map_err(|e| case_error(path, &location, e));
+        cases.push((case, identity, location));
+    }
+    describe_cases(path, cases)
+}
+
+/// Load test cases from the JSON file (the file contents must be an array). Same as [`yaml`], but
+/// uses strict JSON parser, so syntax errors point to the exact place in the file.
+pub fn json<T: DeserializeOwned + TestNameWithDefault + Send + 'static>(
+    path: &str,
+) -> Vec<DataTestCaseDesc<Result<T, String>>> {
+    let input = std::fs::read_to_string(Path::new(path))
+        .unwrap_or_else(|_| panic!("cannot read file '{}'", path));
+
+    // Raw values borrow from the input, so we know where each test case starts
+    let index: Vec<&RawValue> = serde_json::from_str(&input)
+        .unwrap_or_else(|e| panic!("cannot parse test cases in '{}': {}", path, e));
+
+    let cases = index
+        .into_iter()
+        .map(|raw| {
+            // Already parsed successfully above
+            let value: serde_json::Value = serde_json::from_str(raw.get()).unwrap();
+            let identity = CaseIdentity {
+                id: json_id(&value),
+                contents: value.to_string(),
+            };
+            let location = format!("line {}", line_at(&input, raw.get()));
+            // Parse the text again, so duplicate keys are rejected and errors point to the place in
+            // the test case
+            let case = serde_json::from_str(raw.get()).map_err(|e| case_error(path, &location, e));
+            (case, identity, location)
+        })
+        .collect();
+    describe_cases(path, cases)
+}
+
+/// Load test cases from the TOML file, where each test case is a `[[case]]` table:
+///
+/// ```toml
+/// [[case]]
+/// name = "Pino"
+/// expected = "Hi, Pino!"
+/// ```
+pub fn toml<T: DeserializeOwned + TestNameWithDefault + Send + 'static>(
+    path: &str,
+) -> Vec<DataTestCaseDesc<Result<T, String>>> {
+    let input = std::fs::read_to_string(Path::new(path))
+        .unwrap_or_else(|_| panic!("cannot read file '{}'", path));
+    let cases: TomlCases = ::toml::from_str(&input)
+        .unwrap_or_else(|e| panic!("cannot parse test cases in '{}': {}", path, e));
+
+    let cases = cases
+        .0
+        .into_iter()
+        .map(|value| {
+            // Span starts at the `[[case]]` header
+            let location = format!("line {}", line_at(&input, &input[value.span()]));
+            let value = value.into_inner();
+            let id = match value.get("id") {
+                Some(::toml::Value::String(id)) => Some(id.clone()),
+                Some(::toml::Value::Integer(id)) => Some(id.to_string()),
+                _ => None,
+            };
+            let identity = CaseIdentity {
+                id,
+                contents: value.to_string(),
+            };
+            let case = value.try_into().map_err(|e| case_error(path, &location, e));
+            (case, identity, location)
+        })
+        .collect();
+    describe_cases(path, cases)
+}
+
+/// Load test cases from the CSV file (or TSV file, if the path ends with `.tsv`), where each row
+/// is a test case. Header row gives the names of the fields. If there is an `id` or a `name`
+/// column, its value is used as the test case name. Malformed row (like the row with a wrong number
+/// of fields) only fails its own test.
+pub fn csv<T: DeserializeOwned + TestNameWithDefault + Send + 'static>(
+    path: &str,
+) -> Vec<DataTestCaseDesc<Result<T, String>>> {
+    let delimiter = if path.ends_with(".tsv") { b'\t' } else { b',' };
+    let mut reader = ::csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .from_path(path)
+        .unwrap_or_else(|_| panic!("cannot read file '{}'", path));
+    let headers = reader
+        .headers()
+        .unwrap_or_else(|e| panic!("cannot parse test cases in '{}': {}", path, e))
+        .clone();
+    let id_column = headers
+        .iter()
+        .position(|header| header == "id")
+        .or_else(|| headers.iter().position(|header| header == "name"));
+
+    let cases = reader
+        .records()
+        .map(|record| {
+            let record = match record {
+                Ok(record) => record,
+                // Reading fails the same way again, so the rest of the file is never read
+                Err(e) if e.is_io_error() => panic!("cannot read file '{}': {}", path, e),
+                Err(e) => {
+                    let location = format!("row {}", csv_row(e.position()));
+                    return malformed_case(case_error(path, &location, e), location);
+                }
+            };
+            let location = format!("row {}", csv_row(record.position()));
+            let case = record
+                .deserialize(Some(&headers))
+                .map_err(|e| case_error(path, &location, e));
+            let identity = CaseIdentity {
+                id: id_column.and_then(|idx| record.get(idx)).map(String::from),
+                contents: record.iter().collect::<Vec<_>>().join("\t"),
+            };
+            (case, identity, location)
+        })
+        .collect();
+    describe_cases(path, cases)
+}
+
+/// Row of the CSV record, where the header is the first row (same as in spreadsheets).
+fn csv_row(position: Option<&::csv::Position>) -> u64 {
+    position.map_or(0, |pos| pos.record() + 1)
+}
+
+/// Test case which could not be parsed at all, so there is nothing to name it after but its
+/// location.
+fn malformed_case<T>(error: String, location: String) -> (Result<T, String>, CaseIdentity, String) {
+    let identity = CaseIdentity {
+        id: None,
+        contents: location.clone(),
+    };
+    (Err(error), identity, location)
+}
+
+/// Error message for the test case at the given location which cannot be loaded.
+fn case_error(path: &str, location: &str, error: impl fmt::Display) -> String {
+    format!(
+        "cannot parse test case at {} in '{}': {}",
+        location, path, error
+    )
+}
+
+/// What makes the test case unique, used to give it a name which does not change when other test
+/// cases are added or removed.
+struct CaseIdentity {
+    /// Explicit identifier: mapping key, `id` field or column
+    id: Option<String>,
+    /// Contents of the test case, hashed if there is no better name
+    contents: String,
+}
+
+/// Give each test case a stable name: its explicit identifier, its [`ToString`] representation or
+/// the hash of its contents, in that order. If [`ToString`] gives the same name for few test cases,
+/// it is followed by the hash to tell them apart.
+///
+/// Test cases which failed to deserialize (`Err` with the error message) are returned as they are,
+/// so each of them is reported as a failing test.
+fn describe_cases<T: TestNameWithDefault>(
+    path: &str,
+    cases: Vec<(Result<T, String>, CaseIdentity, String)>,
+) -> Vec<DataTestCaseDesc<Result<T, String>>> {
+    let names = cases
+        .iter()
+        .map(|(case, identity, _)| {
+            let hash = content_hash(&identity.contents);
+            let name = case.as_ref().ok().and_then(TestNameWithDefault::name);
+            match (&identity.id, name) {
+                (Some(id), _) => (id.clone(), None),
+                (None, Some(name)) => (name, Some(hash)),
+                (None, None) => (hash, None),
+            }
+        })
+        .collect::<Vec<_>>();
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for (name, _) in &names {
+        *counts.entry(name).or_default() += 1;
+    }
+    let names = names
+        .iter()
+        .map(|(name, hash)| match hash {
+            Some(hash) if counts[name.as_str()] > 1 => format!("{}#{}", name, hash),
+            _ => name.clone(),
+        })
+        .collect::<Vec<_>>();
+
+    // Test case name to its location
+    let mut seen: HashMap<&str, &str> = HashMap::new();
+    for (name, (_, _, location)) in names.iter().zip(&cases) {
+        if let Some(other) = seen.insert(name, location) {
+            panic!(
+                "two test cases in '{}' have the same name '{}': at {} and at {}",
+                path, name, other, location
+            );
+        }
+    }
+
+    names
         .into_iter()
         .zip(cases)
-        .map(|(marker, case)| DataTestCaseDesc {
-            name: TestNameWithDefault::name(&case),
+        .map(|(name, (case, _, location))| DataTestCaseDesc {
             case,
-            location: format!("line {}", marker.line()),
+            name: Some(name),
+            location,
         })
         .collect()
 }
 
+/// Short hash of the test case contents (FNV-1a), which is the same on all platforms and Rust
+/// versions.
+fn content_hash(contents: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in contents.bytes() {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{:016x}", hash)[..8].to_string()
+}
+
+/// Value of the `id` field of the YAML test case.
+fn yaml_id(value: &serde_yaml::Value) -> Option<String> {
+    match value.get("id")? {
+        serde_yaml::Value::String(id) => Some(id.clone()),
+        serde_yaml::Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+/// Value of the `id` field of the JSON test case.
+fn json_id(value: &serde_json::Value) -> Option<String> {
+    match value.get("id")? {
+        serde_json::Value::String(id) => Some(id.clone()),
+        serde_json::Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+/// Line number (starting from `1`) where the given slice of the source starts.
+fn line_at(source: &str, slice: &str) -> usize {
+    let offset = slice.as_ptr() as usize - source.as_ptr() as usize;
+    source[..offset].matches('\n').count() + 1
+}
+
 /// Trait abstracting two scenarios: test case implementing [`ToString`] and test case not
 /// implementing [`ToString`].
 #[doc(hidden)]
@@ -120,25 +551,52 @@ where
     }
 }
 
-/// Build an index from the YAML source to the location of each test case (top level array elements).
-fn index_cases(source: &str) -> Vec<Marker> {
+/// Location of the test case in the YAML source.
+struct IndexEntry {
+    marker: Marker,
+    /// Mapping key, if test cases are given as a mapping
+    key: Option<String>,
+}
+
+/// Build an index from the YAML source to the location of each test case: top level array elements
+/// or mapping entries (`level` is `1`) or documents of the stream (`level` is `0`). For mapping
+/// entries, location is the location of the key.
+fn index_cases(source: &str, level: usize) -> Result<Vec<IndexEntry>, yaml_rust::ScanError> {
     let mut parser = yaml_rust::parser::Parser::new(source.chars());
     let mut index = Vec::new();
     let mut depth = 0;
+    // Test cases are given as a mapping
+    let mut keyed = false;
+    // Key of the mapping entry we are about to see the value of
+    let mut key: Option<IndexEntry> = None;
     loop {
-        let (event, marker) = parser.next().expect("invalid YAML");
+        let (event, marker) = parser.next()?;
+        let is_node = matches!(
+            event,
+            Event::Scalar(..) | Event::MappingStart(_) | Event::SequenceStart(_) | Event::Alias(_)
+        );
+        if is_node && depth == level {
+            if !keyed {
+                index.push(IndexEntry { marker, key: None });
+            } else if let Some(entry) = key.take() {
+                index.push(entry);
+            } else {
+                let name = match event {
+                    Event::Scalar(ref value, _, _, _) => Some(value.clone()),
+                    _ => None,
+                };
+                key = Some(IndexEntry { marker, key: name });
+            }
+        }
         match event {
             Event::StreamEnd => {
                 break;
             }
-            Event::Scalar(_, _, _, _) if depth == 1 => {
-                index.push(marker);
-            }
-            Event::MappingStart(_idx) if depth == 1 => {
-                index.push(marker);
+            Event::MappingStart(_idx) => {
+                keyed |= depth + 1 == level;
                 depth += 1;
             }
-            Event::MappingStart(_idx) | Event::SequenceStart(_idx) => {
+            Event::SequenceStart(_idx) => {
                 depth += 1;
             }
             Event::MappingEnd | Event::SequenceEnd => {
@@ -148,5 +606,5 @@ fn index_cases(source: &str) -> Vec<Marker> {
         }
     }
 
-    index
+    Ok(index)
 }
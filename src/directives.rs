@@ -0,0 +1,133 @@
+//! Header directives controlling individual `#[datatest::files(..)]` test cases, read from the
+//! matched file itself. Directives are comments at the top of the file (either `//` or `#`
+//! comments), for example:
+//!
+//! ```text
+//! // datatest: ignore reason="not supported yet"
+//! // datatest: should-panic "unexpected token"
+//! // datatest: only-if env=CI
+//! ```
+//!
+//! Scanning stops at the first line which is neither blank nor a comment. Unknown directives are
+//! reported as warnings. Directives could be disabled via `directives = false` option.
+use crate::rustc_test::ShouldPanic;
+use std::io::BufRead;
+use std::path::Path;
+
+const PREFIX: &str = "datatest:";
+
+/// Directives collected from the file header.
+#[derive(Default)]
+pub(crate) struct Directives {
+    pub ignore: bool,
+    /// Reason to show for the ignored test
+    pub ignore_message: Option<&'static str>,
+    pub should_panic: Option<ShouldPanic>,
+}
+
+impl Directives {
+    /// Read directives from the header of the file at the given path.
+    pub fn read(path: &Path) -> Result<Directives, String> {
+        let mut reader = crate::archive::open(path).map_err(|e| e.to_string())?;
+        let mut directives = Directives::default();
+        let mut buf = Vec::new();
+        let mut line_no = 0;
+        loop {
+            buf.clear();
+            match reader.read_until(b'\n', &mut buf) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(e) => return Err(e.to_string()),
+            }
+            line_no += 1;
+            let line = String::from_utf8_lossy(&buf);
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let comment = match line.strip_prefix("//").or_else(|| line.strip_prefix('#')) {
+                Some(comment) => comment.trim_start(),
+                None => break,
+            };
+            if let Some(directive) = comment.strip_prefix(PREFIX) {
+                let known = directives
+                    .apply(directive.trim())
+                    .map_err(|e| format!("line {}: {}", line_no, e))?;
+                // Could be a directive of a newer version, don't fail the test because of it
+                if !known {
+                    eprintln!(
+                        "warning: unknown directive `{}` in '{}' (line {})",
+                        directive.trim(),
+                        path.display(),
+                        line_no
+                    );
+                }
+            }
+        }
+        Ok(directives)
+    }
+
+    /// Apply the directive; gives `false` if the directive is unknown.
+    fn apply(&mut self, directive: &str) -> Result<bool, String> {
+        let (name, args) = match directive.find(char::is_whitespace) {
+            Some(pos) => (&directive[..pos], directive[pos..].trim()),
+            None => (directive, ""),
+        };
+        match name {
+            "ignore" => {
+                self.ignore = true;
+                if !args.is_empty() {
+                    let reason = args
+                        .strip_prefix("reason=")
+                        .and_then(unquote)
+                        .ok_or_else(|| format!("expected `reason=\"...\"`, got `{}`", args))?;
+                    self.ignore_message = Some(leak(reason));
+                }
+            }
+            "should-panic" => {
+                self.should_panic = Some(if args.is_empty() {
+                    ShouldPanic::Yes
+                } else {
+                    let message = unquote(args)
+                        .ok_or_else(|| format!("expected quoted message, got `{}`", args))?;
+                    ShouldPanic::YesWithMessage(leak(message))
+                });
+            }
+            "only-if" => {
+                let condition = args
+                    .strip_prefix("env=")
+                    .ok_or_else(|| format!("expected `env=<VAR>`, got `{}`", args))?;
+                // Either `env=VAR` (must be set and non-empty) or `env=VAR=value`
+                let enabled = match condition.split_once('=') {
+                    Some((var, value)) => std::env::var(var).is_ok_and(|v| v == value),
+                    None => std::env::var(condition).is_ok_and(|v| !v.is_empty()),
+                };
+                if !enabled {
+                    self.ignore = true;
+                    self.ignore_message = Some(leak(&format!("only-if env={}", condition)));
+                }
+            }
+            _ => return Ok(false),
+        }
+        Ok(true)
+    }
+}
+
+/// Check if the line is a directive comment (used to skip the header when the file is split into
+/// multiple test cases).
+pub(crate) fn is_directive(line: &str) -> bool {
+    let line = line.trim();
+    line.strip_prefix("//")
+        .or_else(|| line.strip_prefix('#'))
+        .is_some_and(|comment| comment.trim_start().starts_with(PREFIX))
+}
+
+/// Strip double quotes around the value.
+fn unquote(value: &str) -> Option<&str> {
+    value.strip_prefix('"')?.strip_suffix('"')
+}
+
+/// Test descriptors want static strings; we only create few of them once per test run.
+fn leak(value: &str) -> &'static str {
+    Box::leak(value.to_string().into_boxed_str())
+}
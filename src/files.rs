@@ -1,14 +1,86 @@
 //! Support module for `#[datatest::files(..)]`
+use crate::runner::RegularShouldPanic;
 use rustc_test::Bencher;
+use serde::de::DeserializeOwned;
 use std::borrow::Borrow;
+use std::error::Error;
+use std::ops::{Deref, DerefMut};
 use std::path::{Path, PathBuf};
 
 /// Used internally for `#[datatest::files(..)]` tests to distinguish regular tests versus benchmark
 /// tests.
 #[doc(hidden)]
 pub enum FilesTestFn {
-    TestFn(fn(&[PathBuf])),
-    BenchFn(fn(&mut Bencher, &[PathBuf])),
+    TestFn(fn(&[TestArg])),
+    BenchFn(fn(&mut Bencher, &[TestArg])),
+}
+
+/// Argument of the `#[datatest::files(..)]` test function, as given by the test runner to the
+/// trampoline function (see `datatest-derive` proc macro sources for more details).
+#[doc(hidden)]
+#[derive(Clone)]
+pub enum TestArg {
+    /// Path to the data file, matched by the pattern or rendered from the template
+    Path(PathBuf),
+    /// Value of the pattern capture group bound to the argument (`<arg> = capture "<name>"`) or the
+    /// contents of the test case when matched file is split into multiple test cases (`per line`)
+    Text(String),
+}
+
+impl TestArg {
+    pub fn path(&self) -> &Path {
+        match self {
+            TestArg::Path(path) => path,
+            TestArg::Text(_) => panic!("expected path argument, got text"),
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        match self {
+            TestArg::Text(text) => text,
+            TestArg::Path(_) => panic!("expected text argument, got path"),
+        }
+    }
+}
+
+/// How the matched file is split into multiple test cases (`<arg> in "<pattern>" per line`).
+#[doc(hidden)]
+#[derive(Clone, Copy)]
+pub enum Split {
+    /// Each non-blank line is a test case
+    Line,
+    /// Each block of lines separated by blank lines is a test case
+    Record,
+}
+
+impl Split {
+    /// Split file contents into test cases, each with the number of its first line. Directive
+    /// comments at the top of the file are not test cases (unless directives are disabled).
+    pub(crate) fn cases(self, input: &str, directives: bool) -> Vec<(usize, String)> {
+        let mut cases: Vec<(usize, String)> = Vec::new();
+        let mut in_record = false;
+        for (idx, line) in input.lines().enumerate() {
+            if line.trim().is_empty() {
+                in_record = false;
+                continue;
+            }
+            if directives && cases.is_empty() && crate::directives::is_directive(line) {
+                continue;
+            }
+            match self {
+                Split::Record if in_record => {
+                    let (_, record) = cases.last_mut().unwrap();
+                    record.push('\n');
+                    record.push_str(line);
+                }
+                _ => {
+                    cases.push((idx + 1, line.to_string()));
+                    in_record = true;
+                }
+            }
+        }
+        cases
+    }
 }
 
 /// Descriptor used internally for `#[datatest::files(..)]` tests.
@@ -16,52 +88,84 @@ pub enum FilesTestFn {
 pub struct FilesTestDesc {
     pub name: &'static str,
     pub ignore: bool,
+    /// Set by `#[should_panic]` on the test function, applies to every test case
+    pub should_panic: RegularShouldPanic,
+    /// Root directory to scan, relative to the `base_dir`
     pub root: &'static str,
+    /// Directory the paths are relative to (either crate root or workspace root)
+    pub base_dir: &'static str,
     pub params: &'static [&'static str],
     pub pattern: usize,
+    pub glob: bool,
+    pub directories: bool,
+    /// Read directives from the header of the matched files
+    pub directives: bool,
+    /// Indices of the params bound to the capture groups of the pattern; these are passed to the
+    /// test function as strings rather than file paths.
+    pub captures: &'static [usize],
+    /// Split each matched file into multiple test cases; the pattern argument gets the contents of
+    /// the test case instead of the file path
+    pub split: Option<Split>,
+    /// Template for the test case name, rendered from the pattern captures (relative path of the
+    /// matched file is used by default)
+    pub case_name: Option<&'static str>,
+    /// Path to the manifest listing test cases which are expected to fail
+    pub xfail: Option<&'static str>,
+    /// Glob patterns of the paths (relative to the root) to skip
+    pub exclude: &'static [&'static str],
+    /// Skip paths ignored via `.gitignore` or `.ignore` files
+    pub gitignore: bool,
+    /// Maximum depth of the directories to scan (`1` means only the root directory itself)
+    pub max_depth: Option<usize>,
+    pub follow_links: bool,
+    /// Include files and directories with names starting with `.`
+    pub include_hidden: bool,
+    /// Do not scan hidden directories
+    pub prune_hidden: bool,
+    /// Sort directory entries by name, so the order of tests is the same on all platforms
+    pub sort: bool,
     pub ignorefn: Option<fn(&Path) -> bool>,
     pub testfn: FilesTestFn,
     pub source_file: &'static str,
 }
 
-/// Trait defining conversion into a function argument. We use it to convert discovered paths
-/// to test data (captured as `&Path`) into what is expected by the function.
+/// Trait defining conversion into a function argument which borrows from the file contents
+/// (`&str`, `&[u8]`, etc). We use it to convert discovered paths to test data (captured as `&Path`)
+/// into what is expected by the function.
 ///
-/// Why so complex? We need a way to convert an argument received as an element of slice  `&[PathBuf]`
+/// Why so complex? We need a way to convert an argument received as an element of slice `&[TestArg]`
 /// and convert it into what the function expects.
 ///
 /// The difficulty here is that for owned arguments we can create value and just pass it down to the
-/// function. However, for arguments taking slices, we need to store value somewhere on the stack
-/// and pass a reference.
+/// function (see [`FromTestFile`]). However, for arguments taking slices, we need to store value
+/// somewhere on the stack and pass a reference.
 ///
-/// Theoretically, our proc macro could generate a different piece of code to handle that, but
-/// to avoid the complexity in proc macro, it always generates code in the form of:
+/// For such arguments (`&T` or `Option<&T>`), our proc macro generates code in the form of:
 ///
 /// ```ignore
-/// TakeArg::take(&mut <#ty as DeriveArg>::derive(&paths_arg[#idx]))
+/// TakeArg::take(&mut <#ty as DeriveArg>::derive(test_args[#idx].path()))
 /// ```
 ///
 /// (`#ty` is the type of the function argument).
 ///
-/// [`DeriveArg`] is responsible for converting `&PathBuf` into an "owned" form of `#ty`. For example,
-/// owned form for both `&str` and `String` will be `String` and conversion will be reading the file
-/// at given path.
+/// [`DeriveArg`] is responsible for converting `&Path` into an "owned" form of `#ty`. For example,
+/// owned form for `&str` will be `String` and conversion will be reading the file at given path.
 ///
 /// [`TakeArg`] is responsible for deriving argument type from the mutable reference to the
-/// `TakeArg::Derived`. The reason mutable referenc is used is because, again, the generated code
-/// is the same, so we cannot take `self` in one case and `&self` in other case. Instead, we take
-/// `&mut self` in `TakeArg` and either convert it to the shared reference or replace the value
-/// with "sentinel" (empty value) and return taken out value as result.
+/// `TakeArg::Derived`. The reason mutable reference is used is because the generated code is the
+/// same for all the argument types, so we cannot take `self` in one case and `&self` in other case.
+/// Instead, we take `&mut self` in `TakeArg` and convert it to the shared reference.
 ///
 /// We pre-define few conversions:
 ///
-/// 1. `&Path` -> `&str`, `String` (reads file content into a string)
-/// 2. `&Path` -> `&[u8]`, `Vec<u8>` (reads file content into a byte buffer)
+/// 1. `&Path` -> `&str` (reads file content into a string)
+/// 2. `&Path` -> `&[u8]` (reads file content into a byte buffer)
 /// 3. `&Path` -> `&Path` (gives path "as is")
+/// 4. `&Path` -> `&T` (loads the value via [`FromTestFile`])
+/// 5. `&Path` -> `Option<T>` (`None` if file does not exist, otherwise uses conversion for `T`)
 ///
-/// Conversion is two step: first, we need to derive some value. Second, we need to either borrow
-/// from that value (if we need `&str`, for example) or take from that value (if we need `String`,
-/// for example) to pass an argument to the function.
+/// Conversion is two step: first, we need to derive some value. Second, we need to borrow from that
+/// value (if we need `&str`, for example) to pass an argument to the function.
 #[doc(hidden)]
 pub trait DeriveArg<'a>: 'a + Sized {
     /// Type to hold temporary value when going from `&Path` into target type.
@@ -75,14 +179,7 @@ pub trait DeriveArg<'a>: 'a + Sized {
 impl<'a> DeriveArg<'a> for &'a str {
     type Derived = String;
     fn derive(path: &'a Path) -> String {
-        crate::read_to_string(path)
-    }
-}
-
-impl<'a> DeriveArg<'a> for String {
-    type Derived = String;
-    fn derive(path: &'a Path) -> String {
-        crate::read_to_string(path)
+        load_arg(path)
     }
 }
 
@@ -91,14 +188,7 @@ impl<'a> DeriveArg<'a> for String {
 impl<'a> DeriveArg<'a> for &'a [u8] {
     type Derived = Vec<u8>;
     fn derive(path: &'a Path) -> Vec<u8> {
-        crate::read_to_end(path)
-    }
-}
-
-impl<'a> DeriveArg<'a> for Vec<u8> {
-    type Derived = Vec<u8>;
-    fn derive(path: &'a Path) -> Vec<u8> {
-        crate::read_to_end(path)
+        load_arg(path)
     }
 }
 
@@ -112,6 +202,30 @@ impl<'a> DeriveArg<'a> for &'a Path {
     }
 }
 
+// Everything else we can load
+
+impl<'a, T: FromTestFile + 'a> DeriveArg<'a> for &'a T {
+    type Derived = T;
+
+    fn derive(path: &'a Path) -> T {
+        load_arg(path)
+    }
+}
+
+// Optional files
+
+impl<'a, T: DeriveArg<'a>> DeriveArg<'a> for Option<T> {
+    type Derived = Option<T::Derived>;
+
+    fn derive(path: &'a Path) -> Option<T::Derived> {
+        if crate::archive::exists(path) {
+            Some(T::derive(path))
+        } else {
+            None
+        }
+    }
+}
+
 #[doc(hidden)]
 pub trait TakeArg<'a, T: 'a> {
     fn take(&'a mut self) -> T;
@@ -129,17 +243,162 @@ where
     }
 }
 
-// Otherwise, take the value & leave the empty one. This is guaranteed (by our proc macro) to
-// be only called once.
+// Optional arguments are taken from the inner value, if there is one.
+
+impl<'a, T: 'a, D: TakeArg<'a, T>> TakeArg<'a, Option<T>> for Option<D> {
+    fn take(&'a mut self) -> Option<T> {
+        self.as_mut().map(TakeArg::take)
+    }
+}
+
+/// Conversion from the test data file into the test function argument of `#[datatest::files(..)]`
+/// test.
+///
+/// Implemented for `String` and `Vec<u8>` (file contents as-is), `PathBuf` (path as-is) and for
+/// [`Deserialized<T>`] (file contents are deserialized into `T`). Implement it for your own types
+/// to use them as arguments (either owned, `T`, or borrowed, `&T`):
+///
+/// ```rust
+/// #![feature(custom_test_frameworks)]
+/// #![test_runner(datatest::runner)]
+///
+/// use std::path::Path;
+///
+/// struct Words(Vec<String>);
+///
+/// impl datatest::FromTestFile for Words {
+///   fn from_test_file(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+///     // Unlike `std::fs::read_to_string`, also works for the test cases read from archives
+///     let contents = String::from_test_file(path)?;
+///     Ok(Words(contents.split_whitespace().map(String::from).collect()))
+///   }
+/// }
+///
+/// #[datatest::files("tests/test-cases", {
+///   input in r"^(.*).input\.txt",
+///   output = r"${1}.output.txt",
+/// })]
+/// fn sample_test(input: &Words, output: Words) {
+///   assert_eq!(input.0.len() + 1, output.0.len());
+/// }
+/// ```
+///
+/// Errors are reported as test failures, together with the path to the file.
+pub trait FromTestFile: Sized {
+    /// Load the value from the file at the given path.
+    fn from_test_file(path: &Path) -> Result<Self, Box<dyn Error>>;
+}
+
+impl FromTestFile for String {
+    fn from_test_file(path: &Path) -> Result<String, Box<dyn Error>> {
+        Ok(crate::read_to_string(path)?)
+    }
+}
+
+impl FromTestFile for Vec<u8> {
+    fn from_test_file(path: &Path) -> Result<Vec<u8>, Box<dyn Error>> {
+        Ok(crate::read_to_end(path)?)
+    }
+}
 
-impl<'a> TakeArg<'a, String> for String {
-    fn take(&mut self) -> String {
-        std::mem::take(self)
+impl FromTestFile for PathBuf {
+    fn from_test_file(path: &Path) -> Result<PathBuf, Box<dyn Error>> {
+        Ok(path.to_path_buf())
     }
 }
 
-impl<'a> TakeArg<'a, Vec<u8>> for Vec<u8> {
-    fn take(&mut self) -> Vec<u8> {
-        std::mem::take(self)
+/// Test function argument deserialized from the file via [`serde`], format is chosen based on the
+/// file extension (`.yaml`/`.yml`, `.json` or `.toml`). Dereferences to the deserialized value:
+///
+/// ```rust
+/// #![feature(custom_test_frameworks)]
+/// #![test_runner(datatest::runner)]
+///
+/// use datatest::Deserialized;
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Greeting {
+///   greeting: String,
+/// }
+///
+/// #[datatest::files("tests/test-cases", {
+///   input in r"^(.*).input\.txt",
+///   greeting = r"${1}.greeting.yaml",
+///   output = r"${1}.output.txt",
+/// })]
+/// fn sample_test(input: &str, greeting: Deserialized<Greeting>, output: &str) {
+///   assert_eq!(format!("{}, {}!", greeting.greeting, input), output);
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Deserialized<T>(pub T);
+
+impl<T> Deserialized<T> {
+    /// Take the deserialized value.
+    pub fn into_inner(self) -> T {
+        self.0
     }
 }
+
+impl<T> Deref for Deserialized<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Deserialized<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: DeserializeOwned> FromTestFile for Deserialized<T> {
+    fn from_test_file(path: &Path) -> Result<Self, Box<dyn Error>> {
+        deserialize(path).map(Deserialized)
+    }
+}
+
+/// Load owned function argument via [`FromTestFile`] (and fail the test if it cannot be loaded).
+/// Proc macro generates the following code for such arguments:
+///
+/// ```ignore
+/// load_arg::<#ty>(test_args[#idx].path())
+/// ```
+///
+/// (or [`load_optional`] if argument is `Option<#ty>`).
+#[doc(hidden)]
+pub fn load_arg<T: FromTestFile>(path: &Path) -> T {
+    T::from_test_file(path)
+        .unwrap_or_else(|e| panic!("cannot load test input at '{}': {}", path.display(), e))
+}
+
+/// Same as [`load_arg`], but gives `None` if file does not exist.
+#[doc(hidden)]
+pub fn load_optional<T: FromTestFile>(path: &Path) -> Option<T> {
+    if crate::archive::exists(path) {
+        Some(load_arg(path))
+    } else {
+        None
+    }
+}
+
+/// Deserialize file contents based on the file extension.
+fn deserialize<T: DeserializeOwned>(path: &Path) -> Result<T, Box<dyn Error>> {
+    let input = crate::read_to_string(path)?;
+    let extension = path.extension().and_then(|ext| ext.to_str()).unwrap_or("");
+    // All the errors include line and column of the error
+    let value = match extension {
+        "yaml" | "yml" => serde_yaml::from_str(&input)?,
+        "json" => serde_json::from_str(&input)?,
+        "toml" => toml::from_str(&input)?,
+        _ => {
+            return Err(
+                "unsupported file extension (expected `yaml`, `yml`, `json` or `toml`)".into(),
+            )
+        }
+    };
+    Ok(value)
+}
@@ -0,0 +1,109 @@
+//! Support for glob patterns in `#[datatest::files(..)]` (`<arg> glob "<pattern>"`)
+use datatest_internals::glob;
+use regex::Regex;
+use std::path::{Component, Path};
+
+pub(crate) use datatest_internals::glob::{escape, to_glob_path, translate_template};
+
+/// Glob pattern matched against file paths relative to the root directory of the test.
+///
+/// Glob is translated into a regular expression where each wildcard becomes a capture group, so
+/// captures could be referenced in templates the same way they are referenced for regular
+/// expressions. The following syntax is supported:
+///
+/// * `*` matches any sequence of characters except `/`
+/// * `?` matches any single character except `/`
+/// * `[...]`, `[!...]` match any character from the given set (or any character not in the set)
+/// * `**` (as a whole path segment) matches any number of directories, including none
+/// * `{name}` is the same as `*`, but the capture group gets a name
+pub(crate) struct Glob {
+    regex: Regex,
+    /// Path segments of the pattern, used to prune directories which cannot contain matches.
+    segments: Vec<Segment>,
+}
+
+enum Segment {
+    /// `**`, matches any number of path segments
+    AnyDirs,
+    /// Regular segment, matches exactly one path segment
+    Pattern(Regex),
+}
+
+impl Glob {
+    pub fn new(pattern: &str) -> Result<Glob, String> {
+        let regex = Regex::new(&glob::translate(pattern)?).map_err(|e| e.to_string())?;
+        let mut segments = Vec::new();
+        for part in pattern.split('/') {
+            if part == "**" {
+                segments.push(Segment::AnyDirs);
+            } else {
+                let segment = format!("^{}$", glob::translate_segment(part, false)?);
+                segments.push(Segment::Pattern(
+                    Regex::new(&segment).map_err(|e| e.to_string())?,
+                ));
+            }
+        }
+        Ok(Glob { regex, segments })
+    }
+
+    /// Regular expression equivalent to this glob pattern.
+    pub fn regex(&self) -> &Regex {
+        &self.regex
+    }
+
+    /// Check if directory at the given path (relative to the root) could contain any paths
+    /// matching this pattern. Used to skip whole subtrees when scanning the root directory.
+    pub fn could_match_within(&self, dir: &Path) -> bool {
+        // We need at least one more segment to match entries inside that directory
+        self.match_segments(dir)
+            .iter()
+            .any(|state| *state < self.segments.len())
+    }
+
+    /// Same as [`Glob::could_match_within`], but also checks if the directory itself could match.
+    pub fn could_match(&self, dir: &Path) -> bool {
+        self.match_segments(dir)
+            .iter()
+            .any(|state| *state <= self.segments.len())
+    }
+
+    /// Match path components one by one against the segments of the pattern and return the set
+    /// of segment indices we could be at after matching all of them.
+    fn match_segments(&self, path: &Path) -> Vec<usize> {
+        let mut states = self.closure(vec![0]);
+        for component in path.components() {
+            let name = match component {
+                Component::Normal(name) => name.to_string_lossy(),
+                _ => continue,
+            };
+            let mut next = Vec::new();
+            for state in states {
+                match self.segments.get(state) {
+                    Some(Segment::AnyDirs) => next.push(state),
+                    Some(Segment::Pattern(re)) if re.is_match(&name) => next.push(state + 1),
+                    _ => {}
+                }
+            }
+            states = self.closure(next);
+            if states.is_empty() {
+                break;
+            }
+        }
+        states
+    }
+
+    /// Extend the set of states with the states reachable by matching `**` against no segments.
+    fn closure(&self, mut states: Vec<usize>) -> Vec<usize> {
+        let mut idx = 0;
+        while idx < states.len() {
+            let state = states[idx];
+            if let Some(Segment::AnyDirs) = self.segments.get(state) {
+                if !states.contains(&(state + 1)) {
+                    states.push(state + 1);
+                }
+            }
+            idx += 1;
+        }
+        states
+    }
+}
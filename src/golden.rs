@@ -0,0 +1,130 @@
+//! Support for golden files (see [`assert_golden`]).
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// Environment variable which turns on the "bless" mode.
+const BLESS_ENV_VAR: &str = "DATATEST_BLESS";
+
+/// Command line flag which turns on the "bless" mode. Removed from the command line by the test
+/// runner before the rest of the options are parsed.
+pub(crate) const BLESS_FLAG: &str = "--bless";
+
+static BLESS: AtomicBool = AtomicBool::new(false);
+
+/// Golden files created or updated during the current test run.
+static BLESSED: Mutex<Vec<(PathBuf, BlessedKind)>> = Mutex::new(Vec::new());
+
+enum BlessedKind {
+    Created,
+    Updated,
+}
+
+/// Turn on "bless" mode for the current test run.
+pub(crate) fn enable_bless() {
+    BLESS.store(true, Ordering::SeqCst);
+}
+
+fn is_bless() -> bool {
+    BLESS.load(Ordering::SeqCst)
+        || std::env::var_os(BLESS_ENV_VAR).is_some_and(|v| !v.is_empty() && v != "0")
+}
+
+/// Compare `actual` output against the contents of the "golden" file at `path` and panic if they
+/// are different.
+///
+/// If tests are run with `DATATEST_BLESS=1` environment variable (or with `--bless` flag passed to
+/// the test harness), golden file is re-written with the `actual` output instead (or created, if
+/// it does not exist yet). All the files created or updated this way are reported once all tests
+/// have finished.
+///
+/// ```rust
+/// #![feature(custom_test_frameworks)]
+/// #![test_runner(datatest::runner)]
+///
+/// #[datatest::files("tests/test-cases", {
+///   input in r"^(.*).input\.txt",
+///   output = r"${1}.output.txt",
+/// })]
+/// fn sample_test(input: &str, output: &std::path::Path) {
+///   datatest::assert_golden(output, format!("Hello, {}!", input));
+/// }
+/// ```
+pub fn assert_golden(path: impl AsRef<Path>, actual: impl AsRef<[u8]>) {
+    let path = path.as_ref();
+    let actual = actual.as_ref();
+    let expected = match std::fs::read(path) {
+        Ok(contents) => Some(contents),
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => panic!("cannot read golden file at '{}': {}", path.display(), e),
+    };
+
+    if expected.as_deref() == Some(actual) {
+        return;
+    }
+
+    if is_bless() {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).unwrap_or_else(|e| {
+                panic!("cannot create directory '{}': {}", parent.display(), e)
+            });
+        }
+        std::fs::write(path, actual)
+            .unwrap_or_else(|e| panic!("cannot write golden file at '{}': {}", path.display(), e));
+        let kind = if expected.is_some() {
+            BlessedKind::Updated
+        } else {
+            BlessedKind::Created
+        };
+        BLESSED
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push((path.to_path_buf(), kind));
+        return;
+    }
+
+    let expected = match expected {
+        Some(expected) => expected,
+        None => panic!(
+            "golden file '{}' does not exist (run tests with `{}=1` to create it)",
+            path.display(),
+            BLESS_ENV_VAR
+        ),
+    };
+    match (std::str::from_utf8(&expected), std::str::from_utf8(actual)) {
+        (Ok(expected), Ok(actual)) => panic!(
+            "golden file '{}' does not match the actual output (run tests with `{}=1` to update it)\n\
+             expected:\n{}\n\
+             actual:\n{}",
+            path.display(),
+            BLESS_ENV_VAR,
+            expected,
+            actual
+        ),
+        _ => panic!(
+            "golden file '{}' does not match the actual output (run tests with `{}=1` to update it)\n\
+             expected {} bytes, got {} bytes",
+            path.display(),
+            BLESS_ENV_VAR,
+            expected.len(),
+            actual.len()
+        ),
+    }
+}
+
+/// Print the list of golden files which were created or updated during this test run.
+pub(crate) fn report_blessed() {
+    let blessed = BLESSED.lock().unwrap_or_else(|e| e.into_inner());
+    if blessed.is_empty() {
+        return;
+    }
+
+    println!("\nblessed golden files:");
+    for (path, kind) in blessed.iter() {
+        let kind = match kind {
+            BlessedKind::Created => "created",
+            BlessedKind::Updated => "updated",
+        };
+        println!("    {}: {}", kind, path.display());
+    }
+}
@@ -19,10 +19,26 @@
 //! Each argument of the test function must be mapped either to the pattern or to the template.
 //! See the example below for the syntax.
 //!
+//! The directory is relative to the crate root (not to the current directory), or to the workspace
+//! root if prefixed with `workspace:` (for example, `"workspace:tests/data"`); the same goes for
+//! other paths given in the attributes. Regular expression pattern is matched against the path
+//! starting with the directory as given in the attribute. The directory, the pattern and the
+//! templates are checked at compile time: templates could only refer to the capture groups defined
+//! by the pattern. Test data files are tracked as dependencies of the test, so test binary is
+//! rebuilt when they change (on nightly, adding or removing files is tracked, too). Problems found
+//! when looking for test cases at runtime (for example, when no files match the pattern) are
+//! reported as a failing test named after the test function, so other tests still run.
+//!
 //! The following argument types are supported:
 //! * `&str`, `String`: capture file contents as string and pass it to the test function
 //! * `&[u8]`, `Vec<u8>`: capture file contents and pass it to the test function
 //! * `&Path`: pass file path as-is
+//! * [`Deserialized<T>`], `&Deserialized<T>` where `T` implements [`serde::de::DeserializeOwned`]:
+//!   deserialize file contents, format is chosen based on the file extension (`.yaml`/`.yml`,
+//!   `.json` or `.toml`)
+//! * `T`, `&T` where `T` implements [`FromTestFile`]: custom conversion from the file
+//! * `Option<T>`, where `T` is any of the types above: `None` if the file derived via template
+//!   does not exist (not allowed for the pattern argument)
 //!
 //! ### Note
 //!
@@ -68,6 +84,233 @@
 //! }
 //! ```
 //!
+//! ### Directives
+//!
+//! Individual test cases could also be controlled by the directives in the header of the matched
+//! file. Directives are `//` or `#` comments at the top of the file, starting with `datatest:`:
+//!
+//! * `// datatest: ignore` or `// datatest: ignore reason="<reason>"`: ignore the test case
+//! * `// datatest: should-panic` or `// datatest: should-panic "<message>"`: test case must panic
+//!   (with the panic message containing the given message)
+//! * `// datatest: only-if env=<VAR>` (or `env=<VAR>=<value>`): ignore the test case unless the
+//!   environment variable is set (or has the given value)
+//!
+//! Unknown directives are reported as warnings. Use `directives = false` option to disable reading
+//! directives (for example, if matched files use the same comment syntax for other purposes).
+//!
+//! `#[should_panic]` on the test function applies to all of its test cases (unless overridden by
+//! the `should-panic` directive), but not to the failure to discover the test cases.
+//!
+//! ### Expected failures
+//!
+//! Test could refer to a manifest file listing test cases which are expected to fail (via
+//! `xfail = "<path>"` option, supported by both `#[datatest::files(..)]` and
+//! `#[datatest::data(..)]` attributes). Each line of the manifest is a glob pattern matching the
+//! names of the test cases (the part of the test name after the name of the test function); lines
+//! starting with `#` are comments. Test cases listed in the manifest must fail: if such test case
+//! passes, it is reported as "unexpectedly passing". Running tests with `DATATEST_XFAIL_UPDATE=1`
+//! environment variable (or passing `--xfail-update` flag to the test harness) re-writes the
+//! manifest with the names of the test cases that actually failed (with wildcards escaped, like
+//! `a[[]1[]].txt`).
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[datatest::files("tests/test-cases", {
+//!   input in r"^(.*).input\.txt",
+//! }, xfail = "tests/files.xfail")]
+//! fn sample_test(input: &str) {
+//!   assert_eq!(input, "Kylie");
+//! }
+//! ```
+//!
+//! ### Excluding paths
+//!
+//! Paths could be excluded from the scan via `exclude` option, either a single glob pattern or a
+//! list of them (matched against the path relative to the test directory; excluded directories
+//! are not scanned at all). With `gitignore = true` option, paths ignored via `.gitignore` and
+//! `.ignore` files (in the test directory and in its parent directories, up to the root of the
+//! repository) are skipped, too:
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[datatest::files("tests/test-cases", {
+//!   input in r"^(.*).input\.txt",
+//!   output = r"${1}.output.txt",
+//! }, exclude = ["target", "**/*.bak"], gitignore = true)]
+//! fn sample_test(input: &str, output: &str) {
+//!   assert_eq!(format!("Hello, {}!", input), output);
+//! }
+//! ```
+//!
+//! ### Scanning options
+//!
+//! The following options control how the test directory is scanned:
+//!
+//! * `max_depth = <int>`: maximum depth of the directories to scan (`1` means only the files
+//!   directly in the test directory)
+//! * `follow_links = <bool>`: follow symbolic links (`true` by default)
+//! * `include_hidden = <bool>`: include files and directories with names starting with `.`
+//!   (`false` by default). Directories with names starting with `.` are still scanned for test
+//!   cases unless `prune_hidden` is set
+//! * `prune_hidden = <bool>`: do not scan directories with names starting with `.` (`false` by
+//!   default)
+//! * `sort = <bool>`: visit directory entries in the order of their names, so the order of tests is
+//!   the same on all platforms (`true` by default)
+//!
+//! ### Archives
+//!
+//! Test directory could be an archive (`.tar`, `.tar.gz`, `.tgz` or `.zip`) instead, in which case
+//! test cases are read straight from it, without extracting it first. Pattern and templates are
+//! matched against the paths inside the archive (as if the archive was a directory) and test names
+//! use these paths, too. Such paths cannot be opened directly, so `&Path` and `PathBuf` arguments
+//! are not allowed; [`FromTestFile`] implementations get these paths as well, so they should read
+//! the file via `String::from_test_file` or `Vec::<u8>::from_test_file` rather than via
+//! [`std::fs`]. Scanning options work the same way, except for `gitignore` and `follow_links`,
+//! which are ignored:
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[datatest::files("tests/archives/greetings.tar.gz", {
+//!   input in r"^(.*)\.input\.txt",
+//!   output = r"${1}.output.txt",
+//! })]
+//! fn sample_test(input: &str, output: &str) {
+//!   assert_eq!(format!("Hello, {}!", input), output);
+//! }
+//! ```
+//!
+//! ### Glob patterns
+//!
+//! Instead of a regular expression, pattern could be defined as a glob, using `glob` instead of
+//! `in`. Glob is matched against the file path relative to the test directory and supports `*`,
+//! `?`, `[...]` and `**` (any number of directories) wildcards. Named wildcards (`{name}`) work
+//! like `*` and could be referenced in templates as `{name}`. All wildcards could also be
+//! referenced by their index (`{1}`, `{2}`, etc). Templates are relative to the test directory,
+//! too. Directories which cannot contain matching files are not scanned at all.
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[datatest::files("tests/test-cases", {
+//!   input glob "{name}.input.txt",
+//!   output = "{name}.output.txt",
+//! })]
+//! fn sample_test(input: &str, output: &str) {
+//!   assert_eq!(format!("Hello, {}!", input), output);
+//! }
+//! ```
+//!
+//! ### Binding captures to arguments
+//!
+//! Arguments could also be bound to the capture groups of the pattern (either named or numbered
+//! ones) via `<arg> = capture "<name>"`. Such arguments get the captured text (as `&str` or
+//! `String`) instead of the file contents:
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[datatest::files("tests/test-cases", {
+//!   input in r"^(.*/)?(?P<case>[^/]*)\.input\.txt",
+//!   case = capture "case",
+//!   output = r"${1}${case}.output.txt",
+//! })]
+//! fn sample_test(input: &str, case: &str, output: &str) {
+//!   assert!(case.starts_with("case-"));
+//!   assert_eq!(format!("Hello, {}!", input), output);
+//! }
+//! ```
+//!
+//! ### Test names
+//!
+//! By default, name of each test is the name of the test function followed by the path of the
+//! matched file relative to the test directory. Use `name = "<template>"` option to render the
+//! name from the pattern captures instead (the syntax is the same as for the other templates).
+//! Whitespace in test names is replaced by `_`, so any test could be selected on the command
+//! line. Test cases must have unique names:
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[datatest::files("tests/test-cases", {
+//!   input glob "{case}.input.txt",
+//!   output = "{case}.output.txt",
+//! }, name = "greeting::{case}")]
+//! fn sample_test(input: &str, output: &str) {
+//!   assert_eq!(format!("Hello, {}!", input), output);
+//! }
+//! ```
+//!
+//! ### Directories as test cases
+//!
+//! With `directories = true` option, pattern is matched against directories instead of files and
+//! a separate test is created for each matching directory. Templates could then refer to files
+//! inside the matched directory:
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[datatest::files("tests/dir-cases", {
+//!   case in r"case-\d+$",
+//!   input = r"${0}/input.txt",
+//!   output = r"${0}/output.txt",
+//! }, directories = true)]
+//! fn sample_test(case: &std::path::Path, input: &str, output: &str) {
+//!   assert_eq!(format!("Hello, {}!", input), output);
+//! }
+//! ```
+//!
+//! ### One test case per line
+//!
+//! Pattern could be followed by `per line` to create a separate test for each non-blank line of
+//! the matched file, or by `per record` to create one for each block of lines separated by blank
+//! lines. The pattern argument then gets the contents of the line (or the record) instead of the
+//! file, so it must be `&str` or `String`. Test names end with the number of the line where the
+//! test case starts (like `sample_test::words.lines::line_12`), and directives in the file header
+//! apply to all the test cases of the file:
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[datatest::files("tests/split-cases", {
+//!   input in r"\.lines$" per line,
+//! })]
+//! fn sample_test(input: &str) {
+//!   assert_eq!(input.chars().rev().collect::<String>(), input);
+//! }
+//! ```
+//!
+//! ### Golden files
+//!
+//! Test could compare its actual output against the file derived via template using
+//! [`assert_golden`]. Running tests with `DATATEST_BLESS=1` environment variable (or passing
+//! `--bless` flag to the test harness) re-writes such "golden" files with the actual output
+//! instead of failing the test. Files created or updated this way are reported at the end of the
+//! test run.
+//!
+//! ```rust
+//! #![feature(custom_test_frameworks)]
+//! #![test_runner(datatest::runner)]
+//!
+//! #[datatest::files("tests/test-cases", {
+//!   input in r"^(.*).input\.txt",
+//!   output = r"${1}.output.txt",
+//! })]
+//! fn sample_test(input: &str, output: &std::path::Path) {
+//!   datatest::assert_golden(output, format!("Hello, {}!", input));
+//! }
+//! ```
+//!
 //! # Data-driven tests
 //!
 //! Second type of tests supported by this crate are "data-driven" tests. These tests define a
@@ -76,8 +319,47 @@
 //! argument type of the test function and a separate test instance is created for it.
 //!
 //! Test function must take exactly one argument and the type of this argument must implement
-//! [`serde::Deserialize`]. Optionally, if this implements [`ToString`] (or [`std::fmt::Display`]),
-//! it's [`ToString::to_string`] result is used to generate test name.
+//! [`serde::Deserialize`]. Test cases are deserialized one by one, so test case which cannot be
+//! deserialized into that type only fails its own test (with the error and the location of the
+//! test case), other test cases run as usual. Only errors which make the whole file unreadable
+//! (like a syntax error in JSON, TOML or YAML file) fail the test named after the test function.
+//!
+//! ### Test case names
+//!
+//! Test names do not depend on where test case is in the file, so they do not change when other
+//! test cases are added or removed. Name of the test case is the first of the following:
+//!
+//! * the mapping key, if test cases are given as a mapping
+//! * the `id` field of the test case (or `id` column, or `name` column, for CSV files)
+//! * the [`ToString::to_string`] result, if test case type implements [`ToString`] (or
+//!   [`std::fmt::Display`]); if it is the same for few test cases, it is followed by the hash of
+//!   the test case contents, like `Pino#1f2e3d4c`
+//! * the hash of the test case contents
+//!
+//! Whitespace in names is replaced by `_`. Location of the test case (like `line 3` or, for CSV
+//! files, `row 2`) is added to the panic message when test fails. Test cases given by custom
+//! loaders without a name are named after their location, in the same format as the test cases of
+//! the split files (`line_3`).
+//!
+//! ### Data formats
+//!
+//! Format of the file with test cases is chosen based on its extension:
+//!
+//! * `.json`: JSON array of test cases
+//! * `.jsonl`, `.ndjson`: JSON Lines file where each non-blank line is a test case
+//! * `.toml`: TOML file where each test case is a `[[case]]` table
+//! * `.csv`, `.tsv`: each row is a test case, header row gives the names of the fields
+//! * anything else: YAML sequence of test cases or YAML mapping where keys are the names of the
+//!   test cases (like `greets_alice: { name: Alice, expected: Hi, Alice! }`)
+//!
+//! YAML stream where each document (separated by `---`) is a test case could be loaded via
+//! `#[datatest::data(datatest::yaml_documents("<path>"))]`. Same as with other loaders called with
+//! a string literal, the path is relative to the crate root (or absolute) and changes to the file
+//! trigger recompilation. Paths given to any other expression are used as is.
+//!
+//! Custom function loading test cases should return `Vec<datatest::DataTestCaseDesc<T>>` or, same
+//! as the loaders of this crate, `Vec<datatest::DataTestCaseDesc<Result<T, String>>>`, where `Err`
+//! is the error message for the test case which cannot be loaded.
 //!
 //! ### `#[test]` attribute
 //!
@@ -113,9 +395,15 @@
 //! For more examples, check the [tests](https://github.com/commure/datatest/blob/master/tests/datatest.rs).
 extern crate test as rustc_test;
 
+mod archive;
 mod data;
+mod directives;
 mod files;
+mod glob;
+mod golden;
 mod runner;
+mod walk;
+mod xfail;
 
 #[cfg(feature = "unsafe_test_runner")]
 mod interceptor;
@@ -128,8 +416,10 @@ mod interceptor {
 /// Internal re-exports for the procedural macro to use.
 #[doc(hidden)]
 pub mod __internal {
-    pub use crate::data::{DataBenchFn, DataTestDesc, DataTestFn};
-    pub use crate::files::{DeriveArg, FilesTestDesc, FilesTestFn, TakeArg};
+    pub use crate::data::{DataBenchFn, DataTestCases, DataTestDesc, DataTestFn};
+    pub use crate::files::{
+        load_arg, load_optional, DeriveArg, FilesTestDesc, FilesTestFn, Split, TakeArg, TestArg,
+    };
     pub use crate::runner::assert_test_result;
     pub use crate::rustc_test::Bencher;
     pub use ctor::{ctor, dtor};
@@ -146,6 +436,8 @@ pub mod __internal {
     pub use datatest_derive::{data_test_case_internal, files_test_case_internal};
 }
 
+pub use crate::files::{Deserialized, FromTestFile};
+pub use crate::golden::assert_golden;
 pub use crate::runner::runner;
 
 // i.e. no TCR, use ctor instead
@@ -163,10 +455,9 @@ pub use datatest_derive::{
 
 /// Experimental functionality.
 #[doc(hidden)]
-pub use crate::data::{yaml, DataTestCaseDesc};
+pub use crate::data::{csv, json, jsonl, toml, yaml, yaml_documents, DataTestCaseDesc};
 
-use std::fs::File;
-use std::io::{BufReader, Read};
+use std::io::Read;
 use std::path::Path;
 
 /// `datatest` test harness entry point. Should be declared in the test module, like in the
@@ -187,23 +478,17 @@ macro_rules! harness {
 }
 
 /// Helper function used internally.
-fn read_to_string(path: &Path) -> String {
+fn read_to_string(path: &Path) -> std::io::Result<String> {
     let mut input = String::new();
-    File::open(path)
-        .map(BufReader::new)
-        .and_then(|mut f| f.read_to_string(&mut input))
-        .unwrap_or_else(|e| panic!("cannot read test input at '{}': {}", path.display(), e));
-    input
+    crate::archive::open(path).and_then(|mut f| f.read_to_string(&mut input))?;
+    Ok(input)
 }
 
 /// Helper function used internally.
-fn read_to_end(path: &Path) -> Vec<u8> {
+fn read_to_end(path: &Path) -> std::io::Result<Vec<u8>> {
     let mut input = Vec::new();
-    File::open(path)
-        .map(BufReader::new)
-        .and_then(|mut f| f.read_to_end(&mut input))
-        .unwrap_or_else(|e| panic!("cannot read test input at '{}': {}", path.display(), e));
-    input
+    crate::archive::open(path).and_then(|mut f| f.read_to_end(&mut input))?;
+    Ok(input)
 }
 
 use crate::rustc_test::TestType;
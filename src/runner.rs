@@ -1,9 +1,16 @@
 use crate::data::{DataTestDesc, DataTestFn};
-use crate::files::{FilesTestDesc, FilesTestFn};
+use crate::directives::Directives;
+use crate::files::{FilesTestDesc, FilesTestFn, TestArg};
+use crate::glob::Glob;
 use crate::rustc_test::{Bencher, ShouldPanic, TestDesc, TestDescAndFn, TestFn, TestName};
+use crate::xfail::Manifest;
+use std::any::Any;
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::fmt;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+use std::sync::Arc;
 
 /// Our own copy of `test::ShouldPanic` to be used on stable channel (using types from `test` crate
 /// is not allowed on stable without `#![feature(test)]`. Pretty much copy-pasted.
@@ -53,12 +60,34 @@ fn derive_test_name(root: &Path, path: &Path, test_name: &str) -> String {
             path.display()
         )
     });
+    case_test_name(test_name, &relative.to_string_lossy())
+}
+
+/// Generate test name from the name of the test function and the name of the test case.
+fn case_test_name(test_name: &str, case_name: &str) -> String {
     let mut test_name = real_name(test_name).to_string();
     test_name += "::";
-    test_name += &relative.to_string_lossy();
+    test_name += &sanitize_test_name(case_name);
     test_name
 }
 
+/// Name of the test case, which is the part of the test name after the test function name.
+fn strip_test_prefix<'a>(test_name: &'a str, prefix: &str) -> &'a str {
+    &test_name[real_name(prefix).len() + 2..]
+}
+
+/// Replace characters which are hard to pass as a test filter on the command line (whitespace and
+/// control characters). Also, use `/` as path separator on all platforms.
+fn sanitize_test_name(name: &str) -> String {
+    name.chars()
+        .map(|ch| match ch {
+            '\\' => '/',
+            ch if ch.is_whitespace() || ch.is_control() => '_',
+            ch => ch,
+        })
+        .collect()
+}
+
 /// When compiling tests, Rust compiler collects all items marked with `#[test_case]` and passes
 /// references to them to the test runner in a slice (like `&[&test_a, &test_b, &test_c]`). Since
 /// we need a different descriptor for our data-driven tests than the standard one, we have two
@@ -107,24 +136,7 @@ pub enum DatatestTestDesc<'a> {
     RegularTest(&'a RegularTestDesc),
 }
 
-/// Helper function to iterate through all the files in the given directory, skipping hidden files,
-/// and return an iterator of their paths.
-fn iterate_directory(path: &Path) -> impl Iterator<Item = PathBuf> {
-    walkdir::WalkDir::new(path)
-        .follow_links(true)
-        .into_iter()
-        .map(Result::unwrap)
-        .filter(|entry| {
-            entry.file_type().is_file()
-                && entry
-                    .file_name()
-                    .to_str()
-                    .map_or(false, |s| !s.starts_with('.')) // Skip hidden files
-        })
-        .map(|entry| entry.path().to_path_buf())
-}
-
-struct FilesBenchFn(fn(&mut Bencher, &[PathBuf]), Vec<PathBuf>);
+struct FilesBenchFn(fn(&mut Bencher, &[TestArg]), Vec<TestArg>);
 
 #[cfg(feature = "rustc_test_TDynBenchFn")]
 impl rustc_test::TDynBenchFn for FilesBenchFn {
@@ -158,77 +170,183 @@ impl<'r> FnMut<(&'r mut Bencher,)> for FilesBenchFn {
 /// Generate standard test descriptors ([`test::TestDescAndFn`]) from the descriptor of
 /// `#[datatest::files(..)]`.
 ///
-/// Scans all files in a given directory, finds matching ones and generates a test descriptor for
-/// each of them.
+/// Scans all files (or directories, if `directories` option is set) in a given directory, finds
+/// matching ones and generates a test descriptor for each of them.
 fn render_files_test(desc: &FilesTestDesc, rendered: &mut Vec<TestDescAndFn>) {
-    let root = Path::new(desc.root).to_path_buf();
+    let base_dir = Path::new(desc.base_dir);
+    let root = base_dir.join(desc.root);
 
     let pattern = desc.params[desc.pattern];
-    let re = regex::Regex::new(pattern)
-        .unwrap_or_else(|_| panic!("invalid regular expression: '{}'", pattern));
+    let glob = if desc.glob {
+        let glob = Glob::new(pattern)
+            .unwrap_or_else(|e| panic!("invalid glob pattern '{}': {}", pattern, e));
+        Some(glob)
+    } else {
+        None
+    };
+    let re = match glob {
+        Some(ref glob) => glob.regex().clone(),
+        None => regex::Regex::new(pattern)
+            .unwrap_or_else(|_| panic!("invalid regular expression: '{}'", pattern)),
+    };
+
+    // Templates for glob patterns use `{name}` syntax to refer to the captures
+    let translate = |template: &'static str| match glob {
+        Some(_) => crate::glob::translate_template(template)
+            .unwrap_or_else(|e| panic!("invalid template '{}': {}", template, e)),
+        None => Cow::Borrowed(template),
+    };
+    let templates = desc
+        .params
+        .iter()
+        .map(|param| translate(param))
+        .collect::<Vec<_>>();
+    let case_name = desc.case_name.map(translate);
+
+    // Test name to the path of the test case that got this name
+    let mut names: HashMap<String, PathBuf> = HashMap::new();
+    let xfail = desc
+        .xfail
+        .map(|path| Arc::new(Manifest::load(Path::new(path))));
+
+    // Archive entries are found in memory instead of scanning the directory
+    let paths: Box<dyn Iterator<Item = PathBuf>> = if crate::archive::is_archive(&root) {
+        Box::new(crate::archive::load(&root).iterate(&root, desc).into_iter())
+    } else {
+        Box::new(crate::walk::iterate_directory(&root, desc, glob.as_ref()))
+    };
 
     let mut found = false;
-    for path in iterate_directory(&root) {
-        let input_path = path.to_string_lossy();
-        if re.is_match(&input_path) {
-            // Generate list of paths to pass to the test function. We generate a `PathBuf` for each
+    for path in paths {
+        // Glob patterns are matched against the path relative to the root directory, regular
+        // expressions are matched against the path relative to the base directory (so it starts
+        // with the root directory as given in the attribute).
+        let relative = path.strip_prefix(&root).unwrap_or(&path);
+        let input_path = match glob {
+            Some(_) => crate::glob::to_glob_path(relative),
+            None => Path::new(desc.root)
+                .join(relative)
+                .to_string_lossy()
+                .into_owned(),
+        };
+        if let Some(captures) = re.captures(&input_path) {
+            // Generate list of arguments to pass to the test function. We generate a `TestArg` for each
             // argument of the test function and pass them to the trampoline function in a slice.
             // See `datatest-derive` proc macro sources for more details.
-            let mut paths = Vec::with_capacity(desc.params.len());
+            let mut args = Vec::with_capacity(desc.params.len());
 
-            let path_str = path.to_string_lossy();
-            for (idx, param) in desc.params.iter().enumerate() {
+            for (idx, template) in templates.iter().enumerate() {
                 if idx == desc.pattern {
                     // Pattern path
-                    paths.push(path.to_path_buf());
+                    args.push(TestArg::Path(path.to_path_buf()));
+                } else if desc.captures.contains(&idx) {
+                    // Captured values are passed as-is (only the capture group, even if the
+                    // pattern does not match the whole path)
+                    let mut value = String::new();
+                    captures.expand(template, &mut value);
+                    args.push(TestArg::Text(value));
                 } else {
-                    let rendered_path = re.replace_all(&path_str, *param);
-                    let rendered_path = Path::new(rendered_path.as_ref()).to_path_buf();
-                    paths.push(rendered_path);
+                    let rendered_path = re.replace_all(&input_path, template.as_ref());
+                    let rendered_path = match glob {
+                        Some(_) => root.join(rendered_path.as_ref()),
+                        None => base_dir.join(rendered_path.as_ref()),
+                    };
+                    args.push(TestArg::Path(rendered_path));
                 }
             }
 
-            let test_name = derive_test_name(&root, &path, desc.name);
+            let test_name = match case_name {
+                Some(ref template) => {
+                    let mut case_name = String::new();
+                    captures.expand(template, &mut case_name);
+                    case_test_name(desc.name, &case_name)
+                }
+                None => derive_test_name(&root, &path, desc.name),
+            };
+            if let Some(other) = names.insert(test_name.clone(), path.clone()) {
+                panic!(
+                    "two test cases have the same name '{}': '{}' and '{}'",
+                    test_name,
+                    other.display(),
+                    path.display()
+                );
+            }
+            let directives = if desc.directories || !desc.directives {
+                Directives::default()
+            } else {
+                Directives::read(&path)
+                    .unwrap_or_else(|e| panic!("invalid directives in '{}': {}", path.display(), e))
+            };
             let ignore = desc.ignore
+                || directives.ignore
                 || desc
                     .ignorefn
                     .map_or(false, |ignore_func| ignore_func(&path));
 
-            let testfn = match desc.testfn {
-                FilesTestFn::TestFn(testfn) => TestFn::DynTestFn(Box::new(move || {
-                    testfn(&paths);
-                    Ok(())
-                })),
-                FilesTestFn::BenchFn(benchfn) => {
-                    TestFn::DynBenchFn(Box::new(FilesBenchFn(benchfn, paths)))
+            // Split file into multiple test cases, each named after its location in the file
+            let cases = match desc.split {
+                Some(split) => {
+                    let input = crate::read_to_string(&path)
+                        .unwrap_or_else(|e| panic!("cannot read '{}': {}", path.display(), e));
+                    split
+                        .cases(&input, desc.directives)
+                        .into_iter()
+                        .map(|(line, contents)| {
+                            let mut args = args.clone();
+                            args[desc.pattern] = TestArg::Text(contents);
+                            let location = sanitize_test_name(&format!("line {}", line));
+                            (format!("{}::{}", test_name, location), args)
+                        })
+                        .collect()
                 }
+                None => vec![(test_name, args)],
             };
 
-            // Generate a standard test descriptor
-            let desc = TestDescAndFn {
-                desc: TestDesc {
-                    name: TestName::DynTestName(test_name),
-                    ignore,
-                    should_panic: ShouldPanic::No,
-                    // Cannot be used on stable: https://github.com/rust-lang/rust/issues/46488
-                    #[cfg(feature = "rustc_test_Allow_fail")]
-                    allow_fail: false,
-                    test_type: crate::test_type(desc.source_file),
-                    no_run: false,
-                    compile_fail: false,
-                    #[cfg(feature = "rustc_test_Ignore_messages")]
-                    ignore_message: None,
-                    source_file: desc.source_file,
-                    // TODO:
-                    start_line: 0,
-                    start_col: 0,
-                    end_col: 0,
-                    end_line: 0,
-                },
-                testfn,
-            };
+            for (test_name, args) in cases {
+                let mut testfn = match desc.testfn {
+                    FilesTestFn::TestFn(testfn) => TestFn::DynTestFn(Box::new(move || {
+                        testfn(&args);
+                        Ok(())
+                    })),
+                    FilesTestFn::BenchFn(benchfn) => {
+                        TestFn::DynBenchFn(Box::new(FilesBenchFn(benchfn, args)))
+                    }
+                };
+                // `// should-panic` directive overrides the `#[should_panic]` of the test function
+                let should_panic = directives
+                    .should_panic
+                    .unwrap_or_else(|| desc.should_panic.into());
+                if let (Some(xfail), ShouldPanic::No) = (&xfail, &should_panic) {
+                    let case = strip_test_prefix(&test_name, desc.name).to_string();
+                    testfn = crate::xfail::wrap(xfail, case, testfn);
+                }
 
-            rendered.push(desc);
+                // Generate a standard test descriptor
+                let desc = TestDescAndFn {
+                    desc: TestDesc {
+                        name: TestName::DynTestName(test_name),
+                        ignore,
+                        should_panic,
+                        // Cannot be used on stable: https://github.com/rust-lang/rust/issues/46488
+                        #[cfg(feature = "rustc_test_Allow_fail")]
+                        allow_fail: false,
+                        test_type: crate::test_type(desc.source_file),
+                        no_run: false,
+                        compile_fail: false,
+                        #[cfg(feature = "rustc_test_Ignore_messages")]
+                        ignore_message: directives.ignore_message,
+                        source_file: desc.source_file,
+                        // TODO:
+                        start_line: 0,
+                        start_col: 0,
+                        end_col: 0,
+                        end_line: 0,
+                    },
+                    testfn,
+                };
+
+                rendered.push(desc);
+            }
             found = true;
         }
     }
@@ -244,20 +362,43 @@ fn render_files_test(desc: &FilesTestDesc, rendered: &mut Vec<TestDescAndFn>) {
 
 fn render_data_test(desc: &DataTestDesc, rendered: &mut Vec<TestDescAndFn>) {
     let prefix_name = real_name(desc.name);
+    let xfail = desc
+        .xfail
+        .map(|path| Arc::new(Manifest::load(Path::new(path))));
 
     let cases = (desc.describefn)();
-    for case in cases {
-        // FIXME: use name provided in `case`...
+    if cases.is_empty() {
+        panic!("no test cases were found!");
+    }
 
+    // Test name to the location of the test case that got this name
+    let mut names: HashMap<String, String> = HashMap::new();
+    for case in cases {
+        // Location is not a part of the name, so the name does not change when the file is edited
         let case_name = if let Some(n) = case.name {
-            format!("{}::{} ({})", prefix_name, n, case.location)
+            format!("{}::{}", prefix_name, sanitize_test_name(&n))
         } else {
-            format!("{}::{}", prefix_name, case.location)
+            format!("{}::{}", prefix_name, sanitize_test_name(&case.location))
         };
+        if let Some(other) = names.insert(case_name.clone(), case.location.clone()) {
+            panic!(
+                "two test cases have the same name '{}': at {} and at {}",
+                case_name, other, case.location
+            );
+        }
 
-        let testfn = match case.case {
+        let location = case.location;
+        let mut testfn = match case.case {
             DataTestFn::TestFn(testfn) => TestFn::DynTestFn(Box::new(move || {
-                testfn();
+                // Panic again, telling where the test case is; original message is kept, so it is
+                // still matched against the expected one of `#[should_panic]`
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(testfn));
+                if let Err(payload) = result {
+                    match panic_message(payload.as_ref()) {
+                        Some(message) => panic!("test case at {} failed: {}", location, message),
+                        None => panic!("test case at {} failed", location),
+                    }
+                }
                 Ok(())
             })),
             DataTestFn::BenchFn(benchfn) => TestFn::DynBenchFn(Box::new(move |b| {
@@ -265,13 +406,18 @@ fn render_data_test(desc: &DataTestDesc, rendered: &mut Vec<TestDescAndFn>) {
                 Ok(())
             })),
         };
+        let should_panic = ShouldPanic::from(desc.should_panic);
+        if let (Some(xfail), ShouldPanic::No) = (&xfail, &should_panic) {
+            let case = strip_test_prefix(&case_name, desc.name).to_string();
+            testfn = crate::xfail::wrap(xfail, case, testfn);
+        }
 
         // Generate a standard test descriptor
         let desc = TestDescAndFn {
             desc: TestDesc {
                 name: TestName::DynTestName(case_name),
                 ignore: desc.ignore,
-                should_panic: ShouldPanic::No,
+                should_panic,
                 #[cfg(feature = "rustc_test_Allow_fail")]
                 allow_fail: false,
                 test_type: crate::test_type(desc.source_file),
@@ -412,7 +558,19 @@ pub fn register(new: &mut RegistrationNode) {
 /// See <https://blog.jrenner.net/rust/testing/2018/07/19/test-in-2018.html>
 #[doc(hidden)]
 pub fn runner(tests: &[&dyn TestDescriptor]) {
-    let args = std::env::args().collect::<Vec<_>>();
+    let mut args = std::env::args().collect::<Vec<_>>();
+    // `--bless` and `--xfail-update` are our own flags, so we need to remove them before standard
+    // options are parsed
+    let args_len = args.len();
+    args.retain(|arg| arg != crate::golden::BLESS_FLAG);
+    if args.len() != args_len {
+        crate::golden::enable_bless();
+    }
+    let args_len = args.len();
+    args.retain(|arg| arg != crate::xfail::UPDATE_FLAG);
+    if args.len() != args_len {
+        crate::xfail::enable_update();
+    }
     let parsed = crate::rustc_test::test::parse_opts(&args);
     let mut opts = match parsed {
         Some(Ok(o)) => o,
@@ -435,14 +593,109 @@ pub fn runner(tests: &[&dyn TestDescriptor]) {
         current = node.next;
     }
 
+    // rustc 1.97.0 changes `run_tests_console` to take the list of tests with their order
+    #[cfg(feature = "rustc_test_TestList")]
+    let rendered =
+        crate::rustc_test::TestList::new(rendered, crate::rustc_test::TestListOrder::Unsorted);
+
     // Run tests via standard runner!
-    match crate::rustc_test::run_tests_console(&opts, rendered) {
+    let result = crate::rustc_test::run_tests_console(&opts, rendered);
+    crate::golden::report_blessed();
+    crate::xfail::update_manifests();
+    match result {
         Ok(true) => {}
         Ok(false) => panic!("Some tests failed"),
         Err(e) => panic!("io error when running tests: {:?}", e),
     }
 }
 
+/// Run test cases discovery, catching any panic (invalid pattern, unreadable directory, malformed
+/// test data file, etc) so it could be reported as a test failure rather than aborting the whole
+/// test run.
+fn discover(render: impl FnOnce(&mut Vec<TestDescAndFn>)) -> Result<Vec<TestDescAndFn>, String> {
+    // Don't print the panic message right away, it is reported once the test runs. Panics of
+    // other threads are still reported by the original hook.
+    let hook = Arc::new(std::panic::take_hook());
+    let discovering = std::thread::current().id();
+    let silenced = hook.clone();
+    std::panic::set_hook(Box::new(move |info| {
+        if std::thread::current().id() != discovering {
+            (*silenced)(info)
+        }
+    }));
+    let _restore = Guard(Some(move || {
+        // Drop our hook first, so we get the only reference to the original one
+        drop(std::panic::take_hook());
+        match Arc::try_unwrap(hook) {
+            Ok(hook) => std::panic::set_hook(hook),
+            Err(hook) => std::panic::set_hook(Box::new(move |info| (*hook)(info))),
+        }
+    }));
+
+    let mut tests = Vec::new();
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| render(&mut tests)));
+
+    match result {
+        Ok(()) => Ok(tests),
+        Err(payload) => Err(panic_message(payload.as_ref())
+            .unwrap_or("unknown error")
+            .to_string()),
+    }
+}
+
+/// Message of the panic, if its payload is a string.
+fn panic_message(payload: &(dyn Any + Send)) -> Option<&str> {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        Some(message)
+    } else {
+        payload.downcast_ref::<String>().map(String::as_str)
+    }
+}
+
+/// Runs the closure when dropped.
+struct Guard<F: FnOnce()>(Option<F>);
+
+impl<F: FnOnce()> Drop for Guard<F> {
+    fn drop(&mut self) {
+        // Panic hook cannot be changed while panicking
+        if let (Some(f), false) = (self.0.take(), std::thread::panicking()) {
+            f()
+        }
+    }
+}
+
+/// Generate failing test, named after the test function, which reports the test cases discovery
+/// error. `#[should_panic]` of the test function does not apply, so the error is never hidden.
+fn discovery_failure(
+    name: &'static str,
+    ignore: bool,
+    source_file: &'static str,
+    message: String,
+) -> TestDescAndFn {
+    TestDescAndFn {
+        desc: TestDesc {
+            name: TestName::StaticTestName(real_name(name)),
+            ignore,
+            should_panic: ShouldPanic::No,
+            #[cfg(feature = "rustc_test_Allow_fail")]
+            allow_fail: false,
+            test_type: crate::test_type(source_file),
+            compile_fail: false,
+            no_run: false,
+            #[cfg(feature = "rustc_test_Ignore_messages")]
+            ignore_message: None,
+            source_file,
+            start_col: 0,
+            start_line: 0,
+            end_col: 0,
+            end_line: 0,
+        },
+        testfn: TestFn::DynTestFn(Box::new(move || {
+            panic!("failed to discover test cases: {}", message)
+        })),
+    }
+}
+
 fn render_test_descriptor(
     input: &dyn TestDescriptor,
     opts: &mut crate::rustc_test::TestOpts,
@@ -457,13 +710,31 @@ fn render_test_descriptor(
             })
         }
         DatatestTestDesc::FilesTest(files) => {
-            render_files_test(files, rendered);
-            adjust_for_test_name(opts, files.name);
-        }
-        DatatestTestDesc::DataTest(data) => {
-            render_data_test(data, rendered);
-            adjust_for_test_name(opts, data.name);
+            match discover(|tests| render_files_test(files, tests)) {
+                Ok(tests) => {
+                    rendered.extend(tests);
+                    adjust_for_test_name(opts, files.name);
+                }
+                Err(message) => rendered.push(discovery_failure(
+                    files.name,
+                    files.ignore,
+                    files.source_file,
+                    message,
+                )),
+            }
         }
+        DatatestTestDesc::DataTest(data) => match discover(|tests| render_data_test(data, tests)) {
+            Ok(tests) => {
+                rendered.extend(tests);
+                adjust_for_test_name(opts, data.name);
+            }
+            Err(message) => rendered.push(discovery_failure(
+                data.name,
+                data.ignore,
+                data.source_file,
+                message,
+            )),
+        },
         DatatestTestDesc::RegularTest(desc) => {
             let desc = desc.clone();
             rendered.push(TestDescAndFn {
@@ -0,0 +1,184 @@
+//! Walking the test directory of `#[datatest::files(..)]` tests.
+use crate::files::FilesTestDesc;
+use crate::glob::Glob;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::path::{Path, PathBuf};
+
+/// Find all files (or directories, if `desc.directories` is set) in the test directory which could
+/// be test cases.
+///
+/// Hidden entries (names starting with `.`) are not test cases unless `desc.include_hidden` is set,
+/// but hidden directories are still scanned unless `desc.prune_hidden` is set. Directories which
+/// cannot contain matches for the `glob`, excluded directories and directories ignored via
+/// `.gitignore` (if turned on) are not scanned at all. Entries are visited in the order of their
+/// names, unless `desc.sort` is unset.
+pub(crate) fn iterate_directory<'a>(
+    path: &'a Path,
+    desc: &'a FilesTestDesc,
+    glob: Option<&'a Glob>,
+) -> impl Iterator<Item = PathBuf> + 'a {
+    let directories = desc.directories;
+    let exclude = desc
+        .exclude
+        .iter()
+        .map(|pattern| {
+            Glob::new(pattern)
+                .unwrap_or_else(|e| panic!("invalid exclude pattern '{}': {}", pattern, e))
+        })
+        .collect::<Vec<_>>();
+    let mut ignores = if desc.gitignore {
+        Some(IgnoreStack::new(path))
+    } else {
+        None
+    };
+
+    let include_hidden = desc.include_hidden;
+    let prune_hidden = desc.prune_hidden && !include_hidden;
+    let mut walker = walkdir::WalkDir::new(path)
+        .follow_links(desc.follow_links)
+        .min_depth(1);
+    if let Some(max_depth) = desc.max_depth {
+        walker = walker.max_depth(max_depth);
+    }
+    if desc.sort {
+        walker = walker.sort_by_file_name();
+    }
+
+    walker
+        .into_iter()
+        .filter_entry(move |entry| {
+            let is_dir = entry.file_type().is_dir();
+            if is_dir && prune_hidden && is_hidden(entry) {
+                return false;
+            }
+            let relative = entry.path().strip_prefix(path).unwrap_or(entry.path());
+            let relative_str = crate::glob::to_glob_path(relative);
+            if exclude
+                .iter()
+                .any(|glob| glob.regex().is_match(&relative_str))
+            {
+                return false;
+            }
+            if let Some(ref mut ignores) = ignores {
+                if ignores.is_ignored(relative, entry.depth(), is_dir) {
+                    return false;
+                }
+            }
+            match glob {
+                Some(glob) if is_dir => {
+                    if directories {
+                        glob.could_match(relative)
+                    } else {
+                        glob.could_match_within(relative)
+                    }
+                }
+                _ => true,
+            }
+        })
+        .map(move |entry| {
+            entry.unwrap_or_else(|e| panic!("cannot scan '{}': {}", path.display(), e))
+        })
+        .filter(move |entry| {
+            if !include_hidden && is_hidden(entry) {
+                return false;
+            }
+            let file_type = entry.file_type();
+            if directories {
+                file_type.is_dir()
+            } else {
+                file_type.is_file()
+            }
+        })
+        .map(|entry| entry.path().to_path_buf())
+}
+
+fn is_hidden(entry: &walkdir::DirEntry) -> bool {
+    entry.file_name().to_string_lossy().starts_with('.')
+}
+
+/// `.gitignore` and `.ignore` files which apply to the entry currently visited by the walker.
+/// Relies on walker visiting entries in depth-first order.
+struct IgnoreStack {
+    /// Absolute path to the test directory
+    root: PathBuf,
+    /// Ignore files from the test directory and its parents (up to the root of the repository)
+    parents: Vec<Gitignore>,
+    /// Ignore files from the directories inside the test directory, together with the depth of
+    /// the directory they were found in (innermost last)
+    stack: Vec<(usize, Gitignore)>,
+}
+
+impl IgnoreStack {
+    fn new(root: &Path) -> IgnoreStack {
+        let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        let mut parents = Vec::new();
+        for dir in root.ancestors() {
+            if let Some(ignore) = load_ignore(dir) {
+                parents.push(ignore);
+            }
+            // Don't go beyond the root of the repository
+            if dir.join(".git").exists() {
+                break;
+            }
+        }
+        IgnoreStack {
+            root,
+            parents,
+            stack: Vec::new(),
+        }
+    }
+
+    /// Check if entry at the given path (relative to the test directory) is ignored. If it is a
+    /// directory that is not ignored, its ignore files are applied to the entries inside it.
+    fn is_ignored(&mut self, relative: &Path, depth: usize, is_dir: bool) -> bool {
+        // Leave directories we are done with
+        while self.stack.last().is_some_and(|(d, _)| *d >= depth) {
+            self.stack.pop();
+        }
+
+        let path = self.root.join(relative);
+        let matchers = self
+            .stack
+            .iter()
+            .rev()
+            .map(|(_, ignore)| ignore)
+            .chain(self.parents.iter());
+        for ignore in matchers {
+            match ignore.matched(&path, is_dir) {
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => break,
+                Match::None => {}
+            }
+        }
+
+        if is_dir {
+            if let Some(ignore) = load_ignore(&path) {
+                self.stack.push((depth, ignore));
+            }
+        }
+        false
+    }
+}
+
+/// Load `.gitignore` and `.ignore` files from the given directory (`.ignore` takes precedence).
+fn load_ignore(dir: &Path) -> Option<Gitignore> {
+    let mut builder = GitignoreBuilder::new(dir);
+    let mut found = false;
+    for name in [".gitignore", ".ignore"] {
+        let path = dir.join(name);
+        if path.is_file() {
+            if let Some(e) = builder.add(&path) {
+                panic!("invalid ignore file '{}': {}", path.display(), e);
+            }
+            found = true;
+        }
+    }
+    if !found {
+        return None;
+    }
+    let ignore = builder
+        .build()
+        .unwrap_or_else(|e| panic!("invalid ignore file in '{}': {}", dir.display(), e));
+    Some(ignore)
+}
@@ -0,0 +1,187 @@
+//! Support for the expected failures manifests (`xfail = "<path>"` option of the
+//! `#[datatest::files(..)]` and `#[datatest::data(..)]` attributes).
+//!
+//! Manifest is a text file listing names of the test cases (or glob patterns matching them) which
+//! are expected to fail, one per line. Test case name is the part of the test name after the name
+//! of the test function. Empty lines and lines starting with `#` are ignored.
+//!
+//! Test cases listed in the manifest pass if they fail and fail if they unexpectedly pass. When
+//! running tests with `DATATEST_XFAIL_UPDATE=1` (or with `--xfail-update` flag), manifest is
+//! re-written with the list of the test cases that failed instead (wildcards in their names are
+//! escaped, like `a[[]1[]].txt`).
+use crate::glob::Glob;
+use crate::rustc_test::TestFn;
+use std::collections::BTreeMap;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Environment variable which turns on the manifest update mode.
+const UPDATE_ENV_VAR: &str = "DATATEST_XFAIL_UPDATE";
+
+/// Command line flag which turns on the manifest update mode. Removed from the command line by the
+/// test runner before the rest of the options are parsed.
+pub(crate) const UPDATE_FLAG: &str = "--xfail-update";
+
+static UPDATE: AtomicBool = AtomicBool::new(false);
+
+/// Outcomes of the test cases managed by each manifest (`true` if test case failed), recorded
+/// during the current test run.
+static OUTCOMES: Mutex<BTreeMap<PathBuf, Outcomes>> = Mutex::new(BTreeMap::new());
+
+struct Outcomes {
+    manifest: Arc<Manifest>,
+    cases: BTreeMap<String, bool>,
+}
+
+/// Turn on manifest update mode for the current test run.
+pub(crate) fn enable_update() {
+    UPDATE.store(true, Ordering::SeqCst);
+}
+
+fn is_update() -> bool {
+    UPDATE.load(Ordering::SeqCst)
+        || std::env::var_os(UPDATE_ENV_VAR).is_some_and(|v| !v.is_empty() && v != "0")
+}
+
+/// Parsed expected failures manifest.
+pub(crate) struct Manifest {
+    path: PathBuf,
+    /// Comment lines at the top of the file, preserved when manifest is updated
+    header: Vec<String>,
+    entries: Vec<Entry>,
+}
+
+struct Entry {
+    line: String,
+    glob: Glob,
+}
+
+impl Manifest {
+    /// Load manifest at the given path. Manifest which does not exist yet is considered empty in
+    /// the update mode.
+    pub fn load(path: &Path) -> Manifest {
+        let input = match std::fs::read_to_string(path) {
+            Ok(input) => input,
+            Err(ref e) if e.kind() == std::io::ErrorKind::NotFound && is_update() => String::new(),
+            Err(e) => panic!("cannot read xfail manifest '{}': {}", path.display(), e),
+        };
+
+        let mut header = Vec::new();
+        let mut entries = Vec::new();
+        for line in input.lines() {
+            let line = line.trim();
+            if line.starts_with('#') && entries.is_empty() {
+                header.push(line.to_string());
+            }
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let glob = Glob::new(line).unwrap_or_else(|e| {
+                panic!("invalid entry '{}' in '{}': {}", line, path.display(), e)
+            });
+            entries.push(Entry {
+                line: line.to_string(),
+                glob,
+            });
+        }
+        Manifest {
+            path: path.to_path_buf(),
+            header,
+            entries,
+        }
+    }
+
+    fn is_listed(&self, case: &str) -> bool {
+        self.entries
+            .iter()
+            .any(|entry| entry.glob.regex().is_match(case))
+    }
+}
+
+/// Wrap test function of the test case managed by the manifest. Test cases listed in the manifest
+/// are expected to fail. Benchmarks are not affected.
+pub(crate) fn wrap(manifest: &Arc<Manifest>, case: String, testfn: TestFn) -> TestFn {
+    let testfn = match testfn {
+        TestFn::DynTestFn(testfn) => testfn,
+        other => return other,
+    };
+    let manifest = manifest.clone();
+    let expected_failure = manifest.is_listed(&case);
+    TestFn::DynTestFn(Box::new(move || {
+        let failed = !matches!(catch_unwind(AssertUnwindSafe(testfn)), Ok(Ok(())));
+        if is_update() {
+            record(&manifest, case, failed);
+            return Ok(());
+        }
+        match (expected_failure, failed) {
+            (true, false) => panic!(
+                "test case is unexpectedly passing (it is listed in '{}' as expected to fail)",
+                manifest.path.display()
+            ),
+            (false, true) => panic!(
+                "test case failed (run tests with `{}=1` to add it to '{}')",
+                UPDATE_ENV_VAR,
+                manifest.path.display()
+            ),
+            _ => Ok(()),
+        }
+    }))
+}
+
+fn record(manifest: &Arc<Manifest>, case: String, failed: bool) {
+    let mut outcomes = OUTCOMES.lock().unwrap_or_else(|e| e.into_inner());
+    outcomes
+        .entry(manifest.path.clone())
+        .or_insert_with(|| Outcomes {
+            manifest: manifest.clone(),
+            cases: BTreeMap::new(),
+        })
+        .cases
+        .insert(case, failed);
+}
+
+/// Re-write manifests of all the test cases that ran during this test run. Entries which do not
+/// match any of the test cases that ran are preserved (so running a subset of tests doesn't lose
+/// them), the rest is replaced by the names of the failed test cases.
+pub(crate) fn update_manifests() {
+    let outcomes = OUTCOMES.lock().unwrap_or_else(|e| e.into_inner());
+    if outcomes.is_empty() {
+        return;
+    }
+
+    println!("\nupdated xfail manifests:");
+    for (path, outcomes) in outcomes.iter() {
+        let mut lines = outcomes
+            .manifest
+            .entries
+            .iter()
+            .filter(|entry| {
+                !outcomes
+                    .cases
+                    .keys()
+                    .any(|case| entry.glob.regex().is_match(case))
+            })
+            .map(|entry| entry.line.clone())
+            .collect::<Vec<_>>();
+        lines.extend(
+            outcomes
+                .cases
+                .iter()
+                .filter(|(_, failed)| **failed)
+                .map(|(case, _)| crate::glob::escape(case)),
+        );
+        lines.sort();
+        lines.dedup();
+
+        let mut contents = String::new();
+        for line in outcomes.manifest.header.iter().chain(lines.iter()) {
+            contents += line;
+            contents.push('\n');
+        }
+        std::fs::write(path, contents)
+            .unwrap_or_else(|e| panic!("cannot write xfail manifest '{}': {}", path.display(), e));
+        println!("    {}: {} expected failures", path.display(), lines.len());
+    }
+}
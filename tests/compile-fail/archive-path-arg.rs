@@ -0,0 +1,6 @@
+#[datatest::__internal::files_test_case_internal("tests/archives/greetings.tar.gz", {
+    input in r"^(.*)\.input\.txt",
+})]
+fn archive_path_arg(input: &std::path::Path) {}
+
+fn main() {}
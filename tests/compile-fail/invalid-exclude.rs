@@ -0,0 +1,6 @@
+#[datatest::__internal::files_test_case_internal("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+}, exclude = "{unclosed")]
+fn invalid_exclude(input: &str) {}
+
+fn main() {}
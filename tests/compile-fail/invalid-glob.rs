@@ -0,0 +1,6 @@
+#[datatest::__internal::files_test_case_internal("tests/test-cases", {
+    input glob "[abc.input.txt",
+})]
+fn invalid_glob(input: &str) {}
+
+fn main() {}
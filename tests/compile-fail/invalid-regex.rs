@@ -0,0 +1,6 @@
+#[datatest::__internal::files_test_case_internal("tests/test-cases", {
+    input in r"^(.*\.input\.txt",
+})]
+fn invalid_regex(input: &str) {}
+
+fn main() {}
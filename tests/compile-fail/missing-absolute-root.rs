@@ -0,0 +1,6 @@
+#[datatest::__internal::files_test_case_internal("/datatest/missing-cases", {
+    input in r"^(.*)\.input\.txt",
+})]
+fn missing_absolute_root(input: &str) {}
+
+fn main() {}
@@ -0,0 +1,6 @@
+#[datatest::__internal::files_test_case_internal("tests/missing-cases", {
+    input in r"^(.*)\.input\.txt",
+})]
+fn missing_root(input: &str) {}
+
+fn main() {}
@@ -0,0 +1,7 @@
+#[datatest::__internal::files_test_case_internal("tests/test-cases", {
+    input in r"^(?P<case>.*)\.input\.txt",
+    name = capture "name",
+})]
+fn undefined_capture_arg(input: &str, name: &str) {}
+
+fn main() {}
@@ -0,0 +1,7 @@
+#[datatest::__internal::files_test_case_internal("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+    output = r"${case}.output.txt",
+})]
+fn undefined_capture(input: &str, output: &str) {}
+
+fn main() {}
@@ -0,0 +1,7 @@
+#[datatest::__internal::files_test_case_internal("tests/test-cases", {
+    input glob "{name}.input.txt",
+    output = "{case}.output.txt",
+})]
+fn undefined_glob_capture(input: &str, output: &str) {}
+
+fn main() {}
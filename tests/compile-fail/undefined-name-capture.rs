@@ -0,0 +1,6 @@
+#[datatest::__internal::files_test_case_internal("tests/test-cases", {
+    input glob "{case}.input.txt",
+}, name = "greeting::{name}")]
+fn undefined_name_capture(input: &str) {}
+
+fn main() {}
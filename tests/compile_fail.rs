@@ -0,0 +1,11 @@
+//! Compile-time diagnostics of the `#[datatest::files]` arguments.
+//!
+//! Public attributes only expand under `#[cfg(test)]`, which trybuild cannot enable, so cases
+//! apply the internal attribute they expand into (only exported with `test_case_registration`).
+#![cfg(all(feature = "rustc_is_nightly", feature = "test_case_registration"))]
+
+#[test]
+fn compile_fail() {
+    let cases = trybuild::TestCases::new();
+    cases.compile_fail("tests/compile-fail/*.rs");
+}
@@ -0,0 +1,111 @@
+//! Failures to discover test cases are reported as a failing test named after the test function.
+//! Such tests are ignored here; tests below run this test binary again to check that each of them
+//! fails with the expected message.
+#![cfg(feature = "rustc_is_nightly")]
+#![feature(custom_test_frameworks)]
+#![test_runner(datatest::runner)]
+
+use std::process::Command;
+
+/// Run the ignored test with the given name in this test binary and check it fails with the
+/// expected message.
+fn assert_discovery_failure(test: &str, message: &str) {
+    let exe = std::env::current_exe().unwrap();
+    let output = Command::new(exe)
+        .args([test, "--exact", "--ignored"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!output.status.success(), "test run passed: {}", stdout);
+    assert!(
+        stdout.contains(&format!("test {} ... FAILED", test)),
+        "test did not fail: {}",
+        stdout
+    );
+    assert!(
+        stdout.contains(&format!("failed to discover test cases: {}", message)),
+        "unexpected failure: {}",
+        stdout
+    );
+}
+
+/// Test cases with the same name
+#[datatest::files("tests/test-cases", {
+    _input glob "{case}.input.txt",
+}, name = "greeting")]
+#[ignore]
+fn files_test_duplicate_name(_input: &str) {}
+
+#[test]
+fn duplicate_name() {
+    assert_discovery_failure(
+        "files_test_duplicate_name",
+        "two test cases have the same name",
+    );
+}
+
+/// No test cases found (`#[should_panic]` does not hide it)
+#[datatest::files("tests/test-cases", {
+    _input in r"\.no-such-extension$",
+})]
+#[ignore]
+#[should_panic]
+fn files_test_no_cases(_input: &str) {}
+
+#[test]
+fn no_cases() {
+    assert_discovery_failure("files_test_no_cases", "no test cases found");
+}
+
+/// Archive which cannot be read (does not break the archive test below)
+#[datatest::files("tests/archives/corrupt.tar.gz", {
+    _input in r"\.txt$",
+})]
+#[ignore]
+fn files_test_corrupt_archive(_input: &str) {}
+
+#[test]
+fn corrupt_archive() {
+    assert_discovery_failure("files_test_corrupt_archive", "cannot read archive");
+}
+
+#[datatest::files("tests/archives/greetings.tar.gz", {
+    input in r"^(.*)\.input\.txt",
+    output = r"${1}.output.txt",
+})]
+fn files_test_archive_after_corrupt(input: &str, output: String) {
+    assert_eq!(format!("Hello, {}!", input), output);
+}
+
+/// Test data file which cannot be parsed
+#[datatest::data("tests/malformed.yaml")]
+#[ignore]
+fn data_test_malformed(_data: String) {}
+
+#[test]
+fn malformed_data() {
+    assert_discovery_failure("data_test_malformed", "cannot parse test cases");
+}
+
+/// Custom loader giving two test cases the same name
+fn duplicate_cases() -> Vec<datatest::DataTestCaseDesc<u32>> {
+    (1..=2)
+        .map(|line| datatest::DataTestCaseDesc {
+            case: line,
+            name: Some("same".to_string()),
+            location: format!("line {}", line),
+        })
+        .collect()
+}
+
+#[datatest::data(duplicate_cases())]
+#[ignore]
+fn data_test_duplicate_name(_data: u32) {}
+
+#[test]
+fn duplicate_data_name() {
+    assert_discovery_failure(
+        "data_test_duplicate_name",
+        "two test cases have the same name 'data_test_duplicate_name::same': at line 1 and at line 2",
+    );
+}
@@ -0,0 +1,203 @@
+//! Bless mode of golden files and update mode of expected failures manifests. Both modes are
+//! turned on for the whole test run and report their results once all tests have finished, so
+//! tests run this test binary again, limited to the test which does the work.
+#![cfg(feature = "rustc_is_nightly")]
+#![feature(custom_test_frameworks)]
+#![test_runner(datatest::runner)]
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Environment variable giving the directory with golden files to the golden tests below; they do
+/// nothing if it is not set.
+const GOLDEN_DIR_ENV: &str = "DATATEST_TEST_GOLDEN_DIR";
+
+/// Run this test binary with the given arguments (test filter and flags), return whether the run
+/// succeeded and its output.
+fn run_self(args: &[&str], golden_dir: Option<&Path>) -> (bool, String) {
+    let exe = std::env::current_exe().unwrap();
+    let mut command = Command::new(exe);
+    command
+        .args(args)
+        .env_remove("DATATEST_BLESS")
+        .env_remove("DATATEST_XFAIL_UPDATE");
+    if let Some(dir) = golden_dir {
+        command.env(GOLDEN_DIR_ENV, dir);
+    }
+    let output = command.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+    (output.status.success(), stdout)
+}
+
+/// Empty temporary directory for the golden files of the test, removed when dropped.
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new(test: &str) -> TempDir {
+        let dir = std::env::temp_dir().join(format!("datatest-{}-{}", test, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        TempDir(dir)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+/// Restores the contents of the file when dropped, so the fixture is not left modified.
+struct Restore(PathBuf, String);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        std::fs::write(&self.0, &self.1).unwrap();
+    }
+}
+
+/// Test cases with wildcards in their names, `true` if test case fails.
+fn wildcard_cases() -> Vec<datatest::DataTestCaseDesc<bool>> {
+    [("a[1].txt", true), ("b{2}", true), ("c*", false)]
+        .iter()
+        .enumerate()
+        .map(|(idx, &(name, fails))| datatest::DataTestCaseDesc {
+            case: fails,
+            name: Some(name.to_string()),
+            location: format!("line {}", idx + 1),
+        })
+        .collect()
+}
+
+#[datatest::data(wildcard_cases(), xfail = "tests/modes/wildcards.xfail")]
+#[test]
+fn child_xfail_wildcards(fails: bool) {
+    assert!(!fails, "test case is expected to fail");
+}
+
+/// Names of the failed test cases are written with wildcards escaped, so they match themselves
+#[test]
+fn xfail_update_escapes_wildcards() {
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/modes/wildcards.xfail");
+    let expected = std::fs::read_to_string(&manifest).unwrap();
+    let _restore = Restore(manifest.clone(), expected.clone());
+    // Entry matching the passing test case is removed
+    std::fs::write(
+        &manifest,
+        "# Test cases with wildcards in their names\nc*\n",
+    )
+    .unwrap();
+
+    let (success, stdout) = run_self(&["child_xfail_wildcards::", "--xfail-update"], None);
+    assert!(success, "test run failed: {}", stdout);
+    assert!(stdout.contains("updated xfail manifests:"), "{}", stdout);
+    assert_eq!(std::fs::read_to_string(&manifest).unwrap(), expected);
+
+    // Updated manifest is loaded back and its entries match the failing test cases
+    let (success, stdout) = run_self(&["child_xfail_wildcards::"], None);
+    assert!(success, "test run failed: {}", stdout);
+}
+
+#[test]
+fn child_golden_bless() {
+    let dir = match std::env::var_os(GOLDEN_DIR_ENV) {
+        Some(dir) => PathBuf::from(dir),
+        None => return,
+    };
+    datatest::assert_golden(dir.join("new/created.txt"), "created");
+    datatest::assert_golden(dir.join("updated.txt"), "updated");
+    datatest::assert_golden(dir.join("same.txt"), "same");
+}
+
+/// In the bless mode, golden files are created or updated and reported once tests have finished
+#[test]
+fn golden_bless() {
+    let dir = TempDir::new("golden-bless");
+    std::fs::write(dir.0.join("updated.txt"), "outdated").unwrap();
+    std::fs::write(dir.0.join("same.txt"), "same").unwrap();
+
+    let (success, stdout) = run_self(&["child_golden_bless", "--exact", "--bless"], Some(&dir.0));
+    assert!(success, "test run failed: {}", stdout);
+    let read = |name: &str| std::fs::read_to_string(dir.0.join(name)).unwrap();
+    assert_eq!(read("new/created.txt"), "created");
+    assert_eq!(read("updated.txt"), "updated");
+    assert_eq!(read("same.txt"), "same");
+
+    let report = &stdout[stdout.find("blessed golden files:").expect(&stdout)..];
+    let created = format!("created: {}", dir.0.join("new/created.txt").display());
+    let updated = format!("updated: {}", dir.0.join("updated.txt").display());
+    assert!(report.contains(&created), "{}", report);
+    assert!(report.contains(&updated), "{}", report);
+    assert!(!report.contains("same.txt"), "{}", report);
+}
+
+#[test]
+fn child_golden_mismatch() {
+    let dir = match std::env::var_os(GOLDEN_DIR_ENV) {
+        Some(dir) => PathBuf::from(dir),
+        None => return,
+    };
+    datatest::assert_golden(dir.join("text.txt"), "actual");
+}
+
+#[test]
+fn child_golden_binary_mismatch() {
+    let dir = match std::env::var_os(GOLDEN_DIR_ENV) {
+        Some(dir) => PathBuf::from(dir),
+        None => return,
+    };
+    datatest::assert_golden(dir.join("binary.bin"), [0xff, 0x00, 0x01]);
+}
+
+#[test]
+fn child_golden_missing() {
+    let dir = match std::env::var_os(GOLDEN_DIR_ENV) {
+        Some(dir) => PathBuf::from(dir),
+        None => return,
+    };
+    datatest::assert_golden(dir.join("missing.txt"), "actual");
+}
+
+/// Outside of the bless mode, golden files are left as they are and the difference is reported
+#[test]
+fn golden_mismatch() {
+    let dir = TempDir::new("golden-mismatch");
+    std::fs::write(dir.0.join("text.txt"), "expected").unwrap();
+    std::fs::write(dir.0.join("binary.bin"), [0xff, 0xfe]).unwrap();
+
+    let (success, stdout) = run_self(&["child_golden_mismatch", "--exact"], Some(&dir.0));
+    assert!(!success, "test run passed: {}", stdout);
+    assert!(
+        stdout.contains(&format!(
+            "golden file '{}' does not match the actual output (run tests with `DATATEST_BLESS=1` \
+             to update it)\nexpected:\nexpected\nactual:\nactual",
+            dir.0.join("text.txt").display()
+        )),
+        "{}",
+        stdout
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.0.join("text.txt")).unwrap(),
+        "expected"
+    );
+
+    let (success, stdout) = run_self(&["child_golden_binary_mismatch", "--exact"], Some(&dir.0));
+    assert!(!success, "test run passed: {}", stdout);
+    assert!(
+        stdout.contains("expected 2 bytes, got 3 bytes"),
+        "{}",
+        stdout
+    );
+
+    let (success, stdout) = run_self(&["child_golden_missing", "--exact"], Some(&dir.0));
+    assert!(!success, "test run passed: {}", stdout);
+    assert!(
+        stdout.contains(&format!(
+            "golden file '{}' does not exist (run tests with `DATATEST_BLESS=1` to create it)",
+            dir.0.join("missing.txt").display()
+        )),
+        "{}",
+        stdout
+    );
+    assert!(!dir.0.join("missing.txt").exists());
+}
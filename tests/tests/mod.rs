@@ -1,3 +1,4 @@
+use datatest::Deserialized;
 use serde::Deserialize;
 use std::fmt;
 use std::path::Path;
@@ -102,6 +103,314 @@ fn files_test_slices(input: &[u8], output: &[u8]) {
     assert_eq!(actual, output);
 }
 
+/// Arguments derived via templates could be optional, `None` is passed if the file does not exist
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+    output = r"${1}.output.txt",
+    missing = r"${1}.missing.txt",
+})]
+#[test]
+fn files_test_optional(input: &str, output: Option<String>, missing: Option<&[u8]>) {
+    assert_eq!(Some(format!("Hello, {}!", input)), output);
+    assert!(missing.is_none());
+}
+
+#[derive(Deserialize)]
+struct Greeting {
+    greeting: String,
+}
+
+/// Any type implementing `serde::Deserialize` could be used as an argument via `Deserialized<T>`,
+/// file is deserialized based on its extension (YAML, JSON or TOML)
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+    yaml = r"${1}.greeting.yaml",
+    json = r"${1}.greeting.json",
+    toml = r"${1}.greeting.toml",
+    output = r"${1}.output.txt",
+})]
+#[test]
+fn files_test_deserialize(
+    input: &str,
+    yaml: Deserialized<Greeting>,
+    json: &Deserialized<Greeting>,
+    toml: Option<Deserialized<Greeting>>,
+    output: &str,
+) {
+    assert_eq!(format!("{}, {}!", yaml.greeting, input), output);
+    assert_eq!(format!("{}, {}!", json.greeting, input), output);
+    assert_eq!(format!("{}, {}!", toml.unwrap().greeting, input), output);
+}
+
+/// Custom argument types could be supported by implementing `datatest::FromTestFile`
+struct Words(Vec<String>);
+
+impl datatest::FromTestFile for Words {
+    fn from_test_file(path: &Path) -> Result<Self, Box<dyn std::error::Error>> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(Words(contents.split_whitespace().map(String::from).collect()))
+    }
+}
+
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+    output = r"${1}.output.txt",
+})]
+#[test]
+fn files_test_custom_type(input: &Words, output: Words) {
+    assert_eq!(output.0, vec![String::from("Hello,"), format!("{}!", input.0[0])]);
+}
+
+/// Pattern could be matched against directories instead of files, one test per directory
+#[datatest::files("tests/dir-cases", {
+    case in r"case-\d+$",
+    input = r"${0}/input.txt",
+    output = r"${0}/output.txt",
+}, directories = true)]
+#[test]
+fn files_test_directories(case: &Path, input: &str, output: &str) {
+    assert!(case.is_dir());
+    assert_eq!(format!("Hello, {}!", input), output);
+}
+
+/// Same, but with a glob pattern
+#[datatest::files("tests/dir-cases", {
+    case glob "{name}",
+    input = "{name}/input.txt",
+}, directories = true)]
+#[test]
+fn files_test_directories_glob(case: &Path, input: &str) {
+    assert!(case.is_dir());
+    assert!(!input.is_empty());
+}
+
+/// Pattern could also be a glob, matched against the path relative to the test directory. Named
+/// wildcards (`{name}`) could be referenced in templates.
+#[datatest::files("tests/test-cases", {
+    input glob "{name}.input.txt",
+    output = "{name}.output.txt",
+})]
+#[test]
+fn files_test_glob(input: &str, output: &str) {
+    assert_eq!(format!("Hello, {}!", input), output);
+}
+
+/// All wildcards of the glob could also be referenced by their index
+#[datatest::files("tests/test-cases", {
+    input glob "**/*.input.txt",
+    output = "{1}{2}.output.txt",
+})]
+#[test]
+fn files_test_glob_indices(input: &str, output: &str) {
+    assert_eq!(format!("Hello, {}!", input), output);
+}
+
+/// Arguments could be bound to the pattern capture groups
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*/)?(?P<case>[^/]*)\.input\.txt",
+    case = capture "case",
+    dir = capture "1",
+    output = r"${1}${case}.output.txt",
+})]
+#[test]
+fn files_test_captures(input: &str, case: &str, dir: String, output: &str) {
+    assert!(case.starts_with("case-"), "unexpected capture: {}", case);
+    assert!(dir.ends_with("test-cases/"), "unexpected capture: {}", dir);
+    assert_eq!(format!("Hello, {}!", input), output);
+}
+
+/// Captures only give the matched group, even if the pattern does not match the whole path
+#[datatest::files("tests/test-cases", {
+    input in r"(?P<case>case-\d+)\.input\.txt",
+    case = capture "case",
+})]
+#[test]
+fn files_test_captures_unanchored(input: &str, case: &str) {
+    assert!(!input.is_empty());
+    assert!(
+        case == "case-01" || case == "case-02",
+        "unexpected capture: {}",
+        case
+    );
+}
+
+/// Same, but with a glob pattern
+#[datatest::files("tests/test-cases", {
+    input glob "{name}.input.txt",
+    name = capture "name",
+})]
+#[test]
+fn files_test_glob_captures(input: &str, name: &str) {
+    assert!(!input.is_empty());
+    assert!(name.starts_with("case-"), "unexpected capture: {}", name);
+}
+
+/// Test names could be rendered from the pattern captures
+#[datatest::files("tests/test-cases", {
+    input glob "{case}.input.txt",
+    output = "{case}.output.txt",
+}, name = "greeting::{case}")]
+#[test]
+fn files_test_custom_name(input: &str, output: &str) {
+    assert_eq!(format!("Hello, {}!", input), output);
+}
+
+/// Test names only include the captures, even if the pattern does not match the whole path;
+/// whitespace is replaced, so the name is easy to pass as a filter
+#[datatest::files("tests/test-cases", {
+    input in r"(?P<case>case-\d+)\.input\.txt",
+}, name = "greeting ${case}")]
+#[test]
+fn files_test_custom_name_unanchored(input: &str) {
+    assert!(!input.is_empty());
+    let name = std::thread::current().name().unwrap_or_default().to_string();
+    assert!(
+        name.ends_with("files_test_custom_name_unanchored::greeting_case-01")
+            || name.ends_with("files_test_custom_name_unanchored::greeting_case-02"),
+        "unexpected test name: {}",
+        name
+    );
+}
+
+/// Test cases could be controlled via directives in the header of the matched file
+#[datatest::files("tests/directives", {
+    input in r"\.txt$",
+})]
+#[test]
+fn files_test_directives(input: &str) {
+    if let Some(message) = input.lines().find_map(|line| line.strip_prefix("panic: ")) {
+        panic!("{}", message);
+    }
+}
+
+/// Directives could be disabled (here, `panics.txt` does not panic, so it would fail if its
+/// `should-panic` directive was applied)
+#[datatest::files("tests/directives", {
+    input in r"\.txt$",
+}, directives = false)]
+#[test]
+fn files_test_no_directives(input: &str) {
+    assert!(!input.is_empty());
+}
+
+/// Test cases listed in the manifest are expected to fail
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+}, xfail = "tests/files.xfail")]
+#[test]
+fn files_test_xfail(input: &str) {
+    assert_eq!(input, "Kylie");
+}
+
+/// Paths could be excluded via glob patterns or via `.gitignore`/`.ignore` files
+#[datatest::files("tests/walk-cases", {
+    input in r"\.txt$",
+}, exclude = ["excluded", "**/*.skip.txt"], gitignore = true)]
+#[test]
+fn files_test_exclude(input: &str) {
+    assert_eq!(input.trim(), "included");
+}
+
+/// Scan could be limited to the given depth (`1` is the test directory itself)
+#[datatest::files("tests/walk-cases", {
+    input in r"\.txt$",
+}, max_depth = 1, sort = true)]
+#[test]
+fn files_test_max_depth(input: &str) {
+    assert_eq!(input.trim(), "included");
+}
+
+/// Hidden files are skipped by default
+#[datatest::files("tests/walk-cases", {
+    input in r"\.hidden\.txt$",
+}, include_hidden = true, follow_links = false)]
+#[test]
+fn files_test_include_hidden(input: &str) {
+    assert_eq!(input.trim(), "hidden");
+}
+
+/// Hidden directories are scanned by default (only hidden files are skipped)
+#[datatest::files("tests/walk-cases", {
+    input in r"/\.config/[^/]*\.txt$",
+})]
+#[test]
+fn files_test_hidden_directories(input: &str) {
+    assert_eq!(input.trim(), "included");
+}
+
+/// ...unless they are pruned
+#[datatest::files("tests/walk-cases", {
+    input in r"\.txt$",
+}, prune_hidden = true)]
+#[test]
+fn files_test_prune_hidden(input: &std::path::Path) {
+    assert!(!input.to_string_lossy().contains(".config"));
+}
+
+/// Paths are relative to the crate root; `workspace:` prefix makes them relative to the root of
+/// the workspace instead
+#[datatest::files("workspace:tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+    output = r"${1}.output.txt",
+})]
+#[test]
+fn files_test_workspace_root(input: &str, output: &str) {
+    assert_eq!(format!("Hello, {}!", input), output);
+}
+
+/// Each line of the matched file is a separate test case (named after the line number)
+#[datatest::files("tests/split-cases", {
+    input in r"\.lines$" per line,
+})]
+#[test]
+fn files_test_per_line(input: &str) {
+    assert!(!input.is_empty());
+    assert_eq!(input.chars().rev().collect::<String>(), input);
+}
+
+/// Blocks of lines separated by blank lines could be test cases, too
+#[datatest::files("tests/split-cases", {
+    input glob "*.records" per record,
+})]
+#[test]
+fn files_test_per_record(input: String) {
+    let (name, greeting) = input.split_once('\n').unwrap();
+    assert_eq!(format!("Hello, {}!", name), greeting);
+}
+
+/// Test cases could be read straight from the archive (test names use paths inside the archive)
+#[datatest::files("tests/archives/greetings.tar.gz", {
+    input in r"^(.*)\.input\.txt",
+    output = r"${1}.output.txt",
+})]
+#[test]
+fn files_test_tar_archive(input: &str, output: String) {
+    assert_eq!(format!("Hello, {}!", input), output);
+}
+
+/// Zip archives are supported, too
+#[datatest::files("tests/archives/greetings.zip", {
+    input glob "**/{case}.input.txt",
+    output = "{1}{case}.output.txt",
+    missing = "{1}{case}.missing.txt",
+})]
+#[test]
+fn files_test_zip_archive(input: &str, output: &str, missing: Option<&str>) {
+    assert_eq!(format!("Hello, {}!", input), output);
+    assert!(missing.is_none());
+}
+
+/// Can compare the output against the "golden" file (re-written when running with
+/// `DATATEST_BLESS=1`, see `tests/modes.rs`)
+#[datatest::files("tests/test-cases", {
+    input in r"^(.*)\.input\.txt",
+    output = r"${1}.output.txt",
+})]
+#[test]
+fn files_test_golden(input: &str, output: &Path) {
+    datatest::assert_golden(output, format!("Hello, {}!", input));
+}
+
 fn is_ignore(path: &Path) -> bool {
     path.display().to_string().ends_with("case-02.input.txt")
 }
@@ -161,9 +470,9 @@ struct GreeterTestCase {
 /// argument (which must implement `serde::Deserialize`). Then, for each element of the vector, a
 /// separate test instance is created and executed.
 ///
-/// Name of each test is derived from the test function module path, test case line number and,
-/// optionall, from the [`ToString`] implementation of the test case data (if either [`ToString`]
-/// or [`std::fmt::Display`] is implemented).
+/// Name of each test is derived from the test function module path and either from the
+/// [`ToString`] implementation of the test case data (if either [`ToString`] or
+/// [`std::fmt::Display`] is implemented) or from the hash of the test case contents.
 #[datatest::data("tests/tests.yaml")]
 #[test]
 fn data_test_line_only(data: &GreeterTestCase) {
@@ -205,6 +514,166 @@ fn data_test_name_and_line(data: &GreeterTestCaseNamed) {
     assert_eq!(data.expected, format!("Hi, {}!", data.name));
 }
 
+/// Explicit `id` field gives the test case name (which does not change when the file is edited)
+#[datatest::data("tests/ids.yaml")]
+#[test]
+fn data_test_ids(data: &GreeterTestCaseNamed) {
+    assert_eq!(data.expected, format!("Hi, {}!", data.name));
+}
+
+/// Test cases could be given as a mapping, keys are used as test case names
+#[datatest::data("tests/keyed.yaml")]
+#[test]
+fn data_test_keyed(data: &GreeterTestCaseNamed) {
+    assert_eq!(data.expected, format!("Hi, {}!", data.name));
+}
+
+/// Location of the keyed test case is the line of its key
+#[test]
+fn data_test_keyed_locations() {
+    let cases = datatest::yaml::<GreeterTestCaseNamed>("tests/keyed.yaml");
+    let cases = cases
+        .iter()
+        .map(|c| (c.name.as_deref().unwrap(), c.location.as_str()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        cases,
+        [
+            ("greets_pino", "line 1"),
+            ("greets_re_l", "line 4"),
+            ("greets vincent", "line 5"),
+        ]
+    );
+}
+
+/// Files with `.json` extension are loaded via `datatest::json`
+#[datatest::data("tests/tests.json")]
+#[test]
+fn data_test_json(data: &GreeterTestCaseNamed) {
+    assert_eq!(data.expected, format!("Hi, {}!", data.name));
+}
+
+/// Location of the JSON test case is the line where it starts
+#[test]
+fn data_test_json_locations() {
+    let cases = datatest::json::<GreeterTestCaseNamed>("tests/tests.json");
+    let locations = cases.iter().map(|c| c.location.as_str()).collect::<Vec<_>>();
+    assert_eq!(locations, ["line 2", "line 6", "line 7"]);
+}
+
+/// Files with `.toml` extension are loaded via `datatest::toml` (each test case is a `[[case]]`
+/// table)
+#[datatest::data("tests/tests.toml")]
+#[test]
+fn data_test_toml(data: &GreeterTestCaseNamed) {
+    assert_eq!(data.expected, format!("Hi, {}!", data.name));
+}
+
+/// Location of the TOML test case is the line of its `[[case]]` header
+#[test]
+fn data_test_toml_locations() {
+    let cases = datatest::toml::<GreeterTestCaseNamed>("tests/tests.toml");
+    let locations = cases.iter().map(|c| c.location.as_str()).collect::<Vec<_>>();
+    assert_eq!(locations, ["line 3", "line 7", "line 12"]);
+}
+
+/// Each row of the `.csv` (or `.tsv`) file is a test case, `name` column gives the name
+#[datatest::data("tests/tests.csv")]
+#[test]
+fn data_test_csv(data: &GreeterTestCase) {
+    assert_eq!(data.expected, format!("Hi, {}!", data.name));
+}
+
+/// Location of the CSV test case is its row, counting the header row
+#[test]
+fn data_test_csv_locations() {
+    let cases = datatest::csv::<GreeterTestCase>("tests/tests.csv");
+    let locations = cases.iter().map(|c| c.location.as_str()).collect::<Vec<_>>();
+    assert_eq!(locations, ["row 2", "row 3", "row 4"]);
+}
+
+#[datatest::data("tests/tests.tsv")]
+#[test]
+fn data_test_tsv(data: GreeterTestCase) {
+    assert_eq!(data.expected, format!("Hi, {}!", data.name));
+}
+
+/// Each line of the `.jsonl` file is a test case
+#[datatest::data("tests/tests.jsonl")]
+#[test]
+fn data_test_jsonl(data: &GreeterTestCaseNamed) {
+    assert_eq!(data.expected, format!("Hi, {}!", data.name));
+}
+
+/// Location of the JSON Lines test case is its line, blank lines are counted, too
+#[test]
+fn data_test_jsonl_locations() {
+    let cases = datatest::jsonl::<GreeterTestCaseNamed>("tests/tests.jsonl");
+    let locations = cases.iter().map(|c| c.location.as_str()).collect::<Vec<_>>();
+    assert_eq!(locations, ["line 1", "line 3", "line 4"]);
+}
+
+/// Each document of the YAML stream is a test case
+#[datatest::data(::datatest::yaml_documents("tests/documents.yaml"))]
+#[test]
+fn data_test_yaml_documents(data: &GreeterTestCaseNamed) {
+    assert_eq!(data.expected, format!("Hi, {}!", data.name));
+}
+
+/// Location of the YAML document is the line where its contents start
+#[test]
+fn data_test_yaml_documents_locations() {
+    let cases = datatest::yaml_documents::<GreeterTestCaseNamed>("tests/documents.yaml");
+    let locations = cases.iter().map(|c| c.location.as_str()).collect::<Vec<_>>();
+    assert_eq!(locations, ["line 3", "line 6", "line 9"]);
+}
+
+/// Test cases listed in the manifest are expected to fail
+#[datatest::data("tests/tests.yaml", xfail = "tests/data.xfail")]
+#[test]
+fn data_test_xfail(data: &GreeterTestCaseNamed) {
+    assert_ne!(data.name, "Re-L");
+}
+
+/// Each test case is deserialized separately, so test case which cannot be deserialized only fails
+/// itself (here, it is listed in the manifest of the expected failures)
+#[datatest::data("tests/malformed-cases.yaml", xfail = "tests/malformed-cases.xfail")]
+#[test]
+fn data_test_malformed_case(data: &GreeterTestCase) {
+    assert_eq!(data.expected, format!("Hi, {}!", data.name));
+}
+
+/// Same for the other formats
+#[datatest::data("tests/malformed-cases.json", xfail = "tests/malformed-cases.xfail")]
+#[test]
+fn data_test_malformed_json_case(data: &GreeterTestCase) {
+    assert_eq!(data.expected, format!("Hi, {}!", data.name));
+}
+
+/// JSON test cases are parsed strictly, errors point to the place in the test case
+#[test]
+fn data_test_malformed_json_errors() {
+    let cases = datatest::json::<GreeterTestCase>("tests/malformed-cases.json");
+    let errors = cases
+        .iter()
+        .filter_map(|c| c.case.as_ref().err())
+        .collect::<Vec<_>>();
+    assert_eq!(errors.len(), 2);
+    assert!(errors[0].contains("invalid type") && errors[0].contains("column"));
+    assert!(errors[1].contains("duplicate field `name`") && errors[1].contains("column"));
+}
+
+/// Malformed test case is loaded as an error, other test cases are still loaded
+#[test]
+fn data_test_malformed_csv_row() {
+    let cases = datatest::csv::<GreeterTestCase>("tests/malformed-cases.csv");
+    let cases = cases
+        .iter()
+        .map(|c| (c.location.as_str(), c.case.is_ok()))
+        .collect::<Vec<_>>();
+    assert_eq!(cases, [("row 2", true), ("row 3", false), ("row 4", true)]);
+}
+
 /// Can also take string inputs
 #[datatest::data("tests/strings.yaml")]
 #[test]
@@ -251,3 +720,40 @@ fn load_test_cases(path: &str) -> Vec<::datatest::DataTestCaseDesc<StringTestCas
 fn data_test_custom(data: StringTestCase) {
     assert_eq!(data.output, format!("Hello, {}!", data.input));
 }
+
+fn load_unnamed_test_cases(path: &str) -> Vec<::datatest::DataTestCaseDesc<StringTestCase>> {
+    let mut cases = load_test_cases(path);
+    for case in &mut cases {
+        case.name = None;
+    }
+    cases
+}
+
+/// Test cases without names are named after their location (in the same format as the test cases
+/// of the split files)
+#[datatest::data(load_unnamed_test_cases("tests/cases.txt"))]
+#[test]
+fn data_test_location_name(data: StringTestCase) {
+    let name = std::thread::current().name().unwrap_or_default().to_string();
+    assert!(
+        name.ends_with("data_test_location_name::line_0")
+            || name.ends_with("data_test_location_name::line_2"),
+        "unexpected test name: {}",
+        name
+    );
+    assert_eq!(data.output, format!("Hello, {}!", data.input));
+}
+
+fn load_first_test_case(path: &str) -> Vec<::datatest::DataTestCaseDesc<StringTestCase>> {
+    let mut cases = load_test_cases(path);
+    cases.truncate(1);
+    cases
+}
+
+/// Panic message of the failing test case tells where the test case is
+#[datatest::data(load_first_test_case("tests/cases.txt"))]
+#[should_panic(expected = "test case at line 0 failed: greeting Pino failed")]
+#[test]
+fn data_test_location_in_panic(data: StringTestCase) {
+    panic!("greeting {} failed", data.input);
+}